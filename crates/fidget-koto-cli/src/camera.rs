@@ -0,0 +1,94 @@
+//! Camera flags, which override the script's `view` hints
+//!
+//! The flags follow `fidget-cli`, see [`fidget_koto::view`] for the meaning
+//! of each one.
+
+use fidget_koto::view::{Projection, ViewHints};
+
+/// Position and size of the rendered region
+#[derive(clap::Args, Debug, Clone)]
+pub struct Framing {
+    /// Scale applied to the model, after translation
+    #[arg(long)]
+    pub scale: Option<f64>,
+
+    /// Translation applied to the model, as `x,y[,z]`
+    #[arg(
+        long,
+        value_delimiter = ',',
+        num_args = 1,
+        allow_negative_numbers = true
+    )]
+    pub center: Option<Vec<f64>>,
+}
+
+impl Framing {
+    /// Returns the script's hints, overridden by the flags
+    pub fn apply(&self, view: &ViewHints) -> anyhow::Result<ViewHints> {
+        let center = match self.center.as_deref() {
+            None => None,
+            Some(&[x, y]) => Some([x, y, 0.0]),
+            Some(&[x, y, z]) => Some([x, y, z]),
+            Some(c) => anyhow::bail!("--center needs 2 or 3 values, got {}", c.len()),
+        };
+        let mut out = view.clone();
+        out.merge(&ViewHints {
+            scale: self.scale,
+            center,
+            ..Default::default()
+        });
+        Ok(out)
+    }
+}
+
+/// Camera for 3D renders
+#[derive(clap::Args, Debug, Clone)]
+pub struct Camera {
+    #[command(flatten)]
+    pub framing: Framing,
+
+    /// Rotation about the X axis, in degrees
+    #[arg(long, allow_negative_numbers = true)]
+    pub pitch: Option<f64>,
+
+    /// Rotation about the Y axis, in degrees
+    #[arg(long, allow_negative_numbers = true)]
+    pub yaw: Option<f64>,
+
+    /// Rotation about the Z axis, in degrees
+    #[arg(long, allow_negative_numbers = true)]
+    pub roll: Option<f64>,
+
+    /// Use an orthographic projection (the default)
+    #[arg(long, conflicts_with = "perspective")]
+    pub isometric: bool,
+
+    /// Use a perspective projection of the given strength
+    #[arg(long)]
+    pub perspective: Option<f64>,
+
+    /// Divides depth by this factor before the perspective is applied
+    #[arg(long)]
+    pub zflatten: Option<f64>,
+}
+
+impl Camera {
+    /// Returns the script's hints, overridden by the flags
+    pub fn apply(&self, view: &ViewHints) -> anyhow::Result<ViewHints> {
+        let projection = match (self.isometric, self.perspective) {
+            (true, _) => Some(Projection::Isometric),
+            (false, Some(p)) => Some(Projection::Perspective(p)),
+            (false, None) => None,
+        };
+        let mut out = self.framing.apply(view)?;
+        out.merge(&ViewHints {
+            pitch: self.pitch,
+            yaw: self.yaw,
+            roll: self.roll,
+            projection,
+            zflatten: self.zflatten,
+            ..Default::default()
+        });
+        Ok(out)
+    }
+}
@@ -0,0 +1,293 @@
+//! Headless rendering and export of Koto models
+//!
+//! Runs a script once, then writes a 2D image, a 3D render, a mesh or an SVG
+//! outline without opening a window or touching the GPU.  The camera defaults
+//! to the script's `view` hints, and flags override individual hints:
+//!
+//! ```text
+//! fidget-koto-cli models/sponge.koto render3d -o sponge.png \
+//!     --mode shaded --scale 0.75 --pitch -25 --yaw -30
+//! fidget-koto-cli models/cabin.koto mesh -o cabin.3mf --scale 0.05 --center=0,0,-14
+//! ```
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use env_logger::Env;
+use fidget_koto::contour::{self, Style, Units};
+use fidget_koto::render::{self, Mode2D, Mode3D};
+use fidget_koto::{Engine, EngineSettings, ScriptContext, mesh};
+use log::info;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+mod camera;
+
+use camera::{Camera, Framing};
+
+#[cfg(feature = "jit")]
+type F = fidget::jit::JitFunction;
+
+#[cfg(not(feature = "jit"))]
+type F = fidget::vm::VmFunction;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Koto script to run
+    script: PathBuf,
+
+    /// Sets a variable declared with `var`, as `name=value`
+    #[arg(long = "var", value_parser = parse_var)]
+    vars: Vec<(String, f64)>,
+
+    /// Enables the Rhai compatibility prelude
+    #[arg(long)]
+    rhai_compat: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Renders the XY plane to a PNG image
+    Render2d {
+        /// Output PNG file
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Width and height of the image, in pixels
+        #[arg(long, default_value_t = 512)]
+        size: u32,
+
+        /// What to draw: color, sdf, exact-sdf or debug
+        #[arg(long, default_value_t = Mode2D::Color)]
+        mode: Mode2D,
+
+        #[command(flatten)]
+        framing: Framing,
+    },
+    /// Renders the shapes in 3D to a PNG image
+    Render3d {
+        /// Output PNG file
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Width, height and depth of the image, in pixels
+        #[arg(long, default_value_t = 512)]
+        size: u32,
+
+        /// What to draw: heightmap, normals or shaded
+        #[arg(long, default_value_t = Mode3D::Shaded)]
+        mode: Mode3D,
+
+        #[command(flatten)]
+        camera: Camera,
+    },
+    /// Meshes the shapes
+    ///
+    /// The format is picked from the extension of the output file: `stl`,
+    /// `obj`, `ply`, `3mf`, `gltf` or `glb`.
+    Mesh {
+        /// Output mesh file
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Octree depth, where each level doubles the resolution
+        #[arg(long, default_value_t = 7)]
+        depth: u8,
+
+        /// Writes ASCII rather than binary STL or PLY files
+        #[arg(long)]
+        ascii: bool,
+
+        /// Writes one file per shape, named after the shape
+        #[arg(long)]
+        per_shape: bool,
+
+        #[command(flatten)]
+        framing: Framing,
+    },
+    /// Contours the XY plane to an SVG file
+    Svg {
+        /// Output SVG file
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Number of grid cells along each side of the image
+        #[arg(long, default_value_t = 256)]
+        resolution: u32,
+
+        /// Height at which shapes are sliced
+        #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+        z: f32,
+
+        /// Physical size of one model unit: mm, cm, in, pt or px
+        #[arg(long, default_value_t = Units::Mm)]
+        units: Units,
+
+        /// Fills shapes instead of outlining them
+        #[arg(long, conflicts_with = "stroke_width")]
+        fill: bool,
+
+        /// Width of outlines, in model units
+        #[arg(long, default_value_t = 0.01)]
+        stroke_width: f32,
+
+        #[command(flatten)]
+        framing: Framing,
+    },
+}
+
+fn parse_var(s: &str) -> Result<(String, f64)> {
+    let (name, value) = s
+        .split_once('=')
+        .with_context(|| format!("expected `name=value`, got `{s}`"))?;
+    let value = value
+        .parse()
+        .with_context(|| format!("invalid value for `{name}`"))?;
+    Ok((name.to_owned(), value))
+}
+
+fn run_script(args: &Args) -> Result<ScriptContext> {
+    let script = std::fs::read_to_string(&args.script)
+        .with_context(|| format!("cannot read {}", args.script.display()))?;
+    let mut settings = EngineSettings::default().with_rhai_compat(args.rhai_compat);
+    for (name, value) in &args.vars {
+        settings = settings.with_var(name, *value);
+    }
+    let start = std::time::Instant::now();
+    let out = Engine::new(settings)
+        .run(&script)
+        .map_err(|e| anyhow::anyhow!("error in {}: {e}", args.script.display()))?;
+    info!(
+        "ran script in {:?}, drawing {} shape(s)",
+        start.elapsed(),
+        out.shapes.len()
+    );
+    if out.shapes.is_empty() {
+        bail!("script did not draw any shapes");
+    }
+    Ok(out)
+}
+
+fn write_png(path: &Path, size: u32, image: &[[u8; 4]]) -> Result<()> {
+    let out = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(out, size, size);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_flattened())?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn write_meshes(path: &Path, meshes: &[mesh::ShapeMesh], ascii: bool) -> Result<()> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let mut out = BufWriter::new(File::create(path)?);
+    match ext.as_deref() {
+        Some("stl") if ascii => mesh::write_stl_ascii(meshes, &mut out)?,
+        Some("stl") => mesh::write_stl(meshes, &mut out)?,
+        Some("obj") => mesh::write_obj(meshes, &mut out)?,
+        Some("ply") if ascii => mesh::write_ply_ascii(meshes, &mut out)?,
+        Some("ply") => mesh::write_ply(meshes, &mut out)?,
+        Some("3mf") => mesh::write_3mf(meshes, &mut out)?,
+        Some("gltf") => mesh::write_gltf(meshes, &mut out)?,
+        Some("glb") => mesh::write_glb(meshes, &mut out)?,
+        _ => bail!("unknown mesh format for {}", path.display()),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Returns `out` with the shape's name (or index) appended to the file stem
+fn part_path(out: &Path, i: usize, mesh: &mesh::ShapeMesh) -> PathBuf {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let part = mesh.name.clone().unwrap_or_else(|| i.to_string());
+    let mut name = format!("{stem}-{part}");
+    if let Some(ext) = out.extension() {
+        name = format!("{name}.{}", ext.to_string_lossy());
+    }
+    out.with_file_name(name)
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let mut ctx = run_script(&args)?;
+
+    let start = std::time::Instant::now();
+    match &args.command {
+        Command::Render2d {
+            out,
+            size,
+            mode,
+            framing,
+        } => {
+            ctx.view = framing.apply(&ctx.view)?;
+            let image = render::render_2d::<F>(&ctx, *mode, *size, *size)?;
+            write_png(out, *size, &image)?;
+        }
+        Command::Render3d {
+            out,
+            size,
+            mode,
+            camera,
+        } => {
+            ctx.view = camera.apply(&ctx.view)?;
+            let image = render::render_3d::<F>(&ctx, *mode, *size, *size)?;
+            write_png(out, *size, &image)?;
+        }
+        Command::Mesh {
+            out,
+            depth,
+            ascii,
+            per_shape,
+            framing,
+        } => {
+            let view = framing.apply(&ctx.view)?;
+            let meshes = ctx.mesh(*depth, view.mesh_bounds())?;
+            if *per_shape {
+                for (i, m) in meshes.iter().enumerate() {
+                    write_meshes(&part_path(out, i, m), std::slice::from_ref(m), *ascii)?;
+                }
+            } else {
+                write_meshes(out, &meshes, *ascii)?;
+            }
+        }
+        Command::Svg {
+            out,
+            resolution,
+            z,
+            units,
+            fill,
+            stroke_width,
+            framing,
+        } => {
+            let bounds = framing.apply(&ctx.view)?.contour_bounds();
+            let contours = ctx.contours(&contour::Settings {
+                bounds,
+                resolution: *resolution,
+                z: *z,
+            })?;
+            let settings = contour::SvgSettings {
+                bounds,
+                units: *units,
+                style: if *fill {
+                    Style::Fill
+                } else {
+                    Style::Stroke(*stroke_width)
+                },
+            };
+            let mut file = BufWriter::new(File::create(out)?);
+            contour::write_svg(&contours, &settings, &mut file)?;
+            file.flush()?;
+        }
+    }
+    info!("wrote output in {:?}", start.elapsed());
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Compile-time evaluation of Koto models
+//!
+//! [`include_model!`] runs a Koto script through [`fidget_koto::Engine`] while
+//! the calling crate is compiled, and embeds the drawn shapes in the binary
+//! using the binary format from [`fidget_koto::io`]:
+//!
+//! ```ignore
+//! let shapes: Vec<fidget_koto::DrawShape> =
+//!     fidget_koto_macros::include_model!("models/part.koto");
+//! ```
+//!
+//! At runtime, the shapes are only deserialized: the script is neither parsed
+//! nor run, and script errors are reported as compile errors instead.  The
+//! calling crate must depend on `fidget-koto` for the deserialization.
+//!
+//! This lives in its own crate rather than being re-exported by
+//! `fidget-koto`, because the macro itself depends on `fidget-koto`.
+
+use fidget_koto::{Engine, EngineSettings, io};
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::PathBuf;
+use std::time::Duration;
+use syn::{LitStr, parse_macro_input};
+
+/// Time limit for evaluating a model at build time
+///
+/// Much longer than the interactive default, since it only costs build time.
+const EXECUTION_LIMIT: Duration = Duration::from_secs(60);
+
+/// Evaluates a Koto model at build time, returning its `Vec<DrawShape>`
+///
+/// The path is relative to the calling crate's `Cargo.toml`.  The crate is
+/// rebuilt when the script changes, but not when a module it imports does.
+#[proc_macro]
+pub fn include_model(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match embed(&path) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn embed(lit: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let error = |msg: String| syn::Error::new(lit.span(), msg);
+
+    let dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| error("CARGO_MANIFEST_DIR is not set".to_owned()))?;
+    let path = PathBuf::from(dir).join(lit.value());
+    let script = std::fs::read_to_string(&path)
+        .map_err(|err| error(format!("cannot read {}: {err}", path.display())))?;
+
+    let mut engine = Engine::new(EngineSettings::default().with_execution_limit(EXECUTION_LIMIT));
+    let out = engine
+        .run(&script)
+        .map_err(|err| error(format!("error in {}: {err}", path.display())))?;
+
+    let mut data = vec![];
+    io::save_shapes(&out.shapes, io::Format::Binary, &mut data)
+        .map_err(|err| error(format!("cannot serialize {}: {err}", path.display())))?;
+
+    let path = path.to_string_lossy();
+    let bytes = proc_macro2::Literal::byte_string(&data);
+    Ok(quote! {{
+        // Makes cargo rebuild the caller when the script changes
+        const _: &[u8] = include_bytes!(#path);
+        ::fidget_koto::io::load_shapes(&mut &#bytes[..])
+            .expect("embedded model should be valid")
+    }})
+}
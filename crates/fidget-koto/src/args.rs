@@ -0,0 +1,519 @@
+//! Argument parsing for builtins
+//!
+//! Builtins declare their parameters as a list of [`Param`]s (or several
+//! lists, for builtins with more than one form), and accept:
+//!
+//! - positional arguments, with optional trailing arguments taking their
+//!   default values: `sphere 1` or `sphere 1, 0, 0, 1`
+//! - a single map of named arguments: `sphere {radius: 1, center: [0, 0, 1]}`
+//! - vectors as `Vec2`/`Vec3`, lists, tuples or runs of numbers:
+//!   `move s, [1, 2, 3]`, `move s, (vec3 1, 2, 3)` or `move s, 1, 2, 3`
+//!
+//! A run of numbers needs at least two components, unless a single number is
+//! copied to all of them (`scale s, 2`).  Runs which could be split between
+//! parameters in several ways, such as `repeat_limited s, 1, 1, 3, 3`, are
+//! rejected.
+//!
+//! When parsing fails, the error lists every accepted form.
+
+use crate::utils::{maybe_points, maybe_tree};
+use crate::vec::{KVec2, KVec3, Scalar};
+use fidget::context::Tree;
+use koto::{prelude::*, runtime, runtime::runtime_error};
+
+/// Kind of value accepted by a parameter
+#[derive(Copy, Clone)]
+pub(crate) enum Kind {
+    /// A `Tree` or shape object
+    Shape,
+    /// A number
+    Number,
+    /// A `Tree`, shape object or number
+    Field,
+    /// A string
+    Str,
+    /// A list of points
+    Points,
+    /// A vector of `len` components
+    ///
+    /// A single component is copied to all components if `splat` is set;
+    /// otherwise, missing components are set to `pad`.  If `numeric` is set,
+    /// all components must be numbers, else they may also be trees.
+    Vector {
+        len: usize,
+        numeric: bool,
+        splat: bool,
+        pad: f64,
+    },
+}
+
+/// Default value of an optional parameter
+#[derive(Copy, Clone)]
+enum Fallback {
+    Number(f64),
+    Vector([f64; 3]),
+    Str(&'static str),
+    /// The parameter may be left out, with no value
+    Null,
+}
+
+/// Parameter of a builtin
+#[derive(Copy, Clone)]
+pub(crate) struct Param {
+    name: &'static str,
+    kind: Kind,
+    default: Option<Fallback>,
+}
+
+impl Param {
+    const fn new(name: &'static str, kind: Kind) -> Self {
+        Self {
+            name,
+            kind,
+            default: None,
+        }
+    }
+
+    /// A `Tree` or shape object
+    pub(crate) const fn shape(name: &'static str) -> Self {
+        Self::new(name, Kind::Shape)
+    }
+
+    /// A number
+    pub(crate) const fn number(name: &'static str) -> Self {
+        Self::new(name, Kind::Number)
+    }
+
+    /// A `Tree`, shape object or number
+    pub(crate) const fn field(name: &'static str) -> Self {
+        Self::new(name, Kind::Field)
+    }
+
+    /// A string
+    pub(crate) const fn string(name: &'static str) -> Self {
+        Self::new(name, Kind::Str)
+    }
+
+    /// A list of points
+    pub(crate) const fn points(name: &'static str) -> Self {
+        Self::new(name, Kind::Points)
+    }
+
+    /// A 2D vector of numbers, with missing components set to 0
+    pub(crate) const fn vec2(name: &'static str) -> Self {
+        Self::new(
+            name,
+            Kind::Vector {
+                len: 2,
+                numeric: true,
+                splat: false,
+                pad: 0.0,
+            },
+        )
+    }
+
+    /// A 3D vector of numbers, with missing components set to 0
+    pub(crate) const fn vec3(name: &'static str) -> Self {
+        Self::new(
+            name,
+            Kind::Vector {
+                len: 3,
+                numeric: true,
+                splat: false,
+                pad: 0.0,
+            },
+        )
+    }
+
+    /// A 3D vector of trees or numbers, with missing components set to 0
+    pub(crate) const fn fields3(name: &'static str) -> Self {
+        Self::new(
+            name,
+            Kind::Vector {
+                len: 3,
+                numeric: false,
+                splat: false,
+                pad: 0.0,
+            },
+        )
+    }
+
+    /// Copies a single vector component to all components
+    pub(crate) const fn splat(mut self) -> Self {
+        if let Kind::Vector { splat, .. } = &mut self.kind {
+            *splat = true;
+        }
+        self
+    }
+
+    /// Sets the value of missing vector components
+    pub(crate) const fn pad(mut self, value: f64) -> Self {
+        if let Kind::Vector { pad, .. } = &mut self.kind {
+            *pad = value;
+        }
+        self
+    }
+
+    /// Makes the parameter optional, with no value if left out
+    pub(crate) const fn optional(mut self) -> Self {
+        self.default = Some(Fallback::Null);
+        self
+    }
+
+    /// Makes the parameter optional, with a default number
+    pub(crate) const fn or_number(mut self, n: f64) -> Self {
+        self.default = Some(Fallback::Number(n));
+        self
+    }
+
+    /// Makes the parameter optional, with a default vector
+    pub(crate) const fn or_vector(mut self, v: [f64; 3]) -> Self {
+        self.default = Some(Fallback::Vector(v));
+        self
+    }
+
+    /// Makes the parameter optional, with a default string
+    pub(crate) const fn or_str(mut self, s: &'static str) -> Self {
+        self.default = Some(Fallback::Str(s));
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Shape => "Tree",
+            Kind::Number => "Number",
+            Kind::Field => "Tree|Number",
+            Kind::Str => "String",
+            Kind::Points => "List of points",
+            Kind::Vector { len: 2, .. } => "Vec2",
+            Kind::Vector { numeric: true, .. } => "Vec3",
+            Kind::Vector { .. } => "Vec3 of Tree|Number",
+        }
+    }
+
+    fn default_value(&self) -> Option<Value> {
+        Some(match self.default? {
+            Fallback::Number(n) => Value::Scalar(Scalar::Number(n)),
+            Fallback::Vector(v) => {
+                let len = match self.kind {
+                    Kind::Vector { len, .. } => len,
+                    _ => 3,
+                };
+                Value::Vector(v[..len].iter().map(|c| Scalar::Number(*c)).collect())
+            }
+            Fallback::Str(s) => Value::Str(s.to_string()),
+            Fallback::Null => Value::Null,
+        })
+    }
+}
+
+enum Value {
+    /// A shape object, along with its tree
+    Shape(KObject, Tree),
+    Scalar(Scalar),
+    Vector(Vec<Scalar>),
+    Str(String),
+    Points(Vec<[f64; 3]>),
+    Null,
+}
+
+/// Parsed arguments, indexed by parameter position
+pub(crate) struct Args {
+    /// Index of the signature which matched
+    pub(crate) form: usize,
+    values: Vec<Value>,
+}
+
+impl Args {
+    /// Returns a `Shape` or `Field` argument as a tree
+    pub(crate) fn tree(&self, i: usize) -> Tree {
+        match &self.values[i] {
+            Value::Shape(_, tree) => tree.clone(),
+            Value::Scalar(s) => s.clone().into_tree(),
+            _ => unreachable!("argument {i} is not a tree"),
+        }
+    }
+
+    /// Returns a `Shape` argument as the object which was passed in
+    pub(crate) fn object(&self, i: usize) -> KObject {
+        match &self.values[i] {
+            Value::Shape(obj, _) => obj.clone(),
+            _ => unreachable!("argument {i} is not a shape"),
+        }
+    }
+
+    /// Returns an optional `Shape` or `Field` argument as a tree
+    pub(crate) fn opt_tree(&self, i: usize) -> Option<Tree> {
+        match &self.values[i] {
+            Value::Null => None,
+            _ => Some(self.tree(i)),
+        }
+    }
+
+    /// Returns a `Number` argument
+    pub(crate) fn number(&self, i: usize) -> f64 {
+        match &self.values[i] {
+            Value::Scalar(Scalar::Number(n)) => *n,
+            _ => unreachable!("argument {i} is not a number"),
+        }
+    }
+
+    /// Returns an optional `Number` argument
+    pub(crate) fn opt_number(&self, i: usize) -> Option<f64> {
+        match &self.values[i] {
+            Value::Null => None,
+            _ => Some(self.number(i)),
+        }
+    }
+
+    /// Returns a `Str` argument
+    pub(crate) fn string(&self, i: usize) -> &str {
+        match &self.values[i] {
+            Value::Str(s) => s.as_str(),
+            _ => unreachable!("argument {i} is not a string"),
+        }
+    }
+
+    /// Returns an optional `Str` argument
+    pub(crate) fn opt_string(&self, i: usize) -> Option<&str> {
+        match &self.values[i] {
+            Value::Null => None,
+            _ => Some(self.string(i)),
+        }
+    }
+
+    /// Returns a `Points` argument
+    pub(crate) fn points(&self, i: usize) -> &[[f64; 3]] {
+        match &self.values[i] {
+            Value::Points(p) => p,
+            _ => unreachable!("argument {i} is not a list of points"),
+        }
+    }
+
+    /// Returns a numeric 2D vector argument
+    pub(crate) fn vec2(&self, i: usize) -> [f64; 2] {
+        let v = self.vec3(i);
+        [v[0], v[1]]
+    }
+
+    /// Returns a numeric vector argument, padded to 3 components
+    pub(crate) fn vec3(&self, i: usize) -> [f64; 3] {
+        match &self.values[i] {
+            Value::Vector(v) => {
+                std::array::from_fn(|k| v.get(k).and_then(Scalar::as_number).unwrap_or(0.0))
+            }
+            _ => unreachable!("argument {i} is not a vector"),
+        }
+    }
+
+    /// Returns an optional numeric vector argument, padded to 3 components
+    pub(crate) fn opt_vec3(&self, i: usize) -> Option<[f64; 3]> {
+        match &self.values[i] {
+            Value::Null => None,
+            _ => Some(self.vec3(i)),
+        }
+    }
+
+    /// Returns a 3D vector argument of trees
+    pub(crate) fn fields3(&self, i: usize) -> [Tree; 3] {
+        match &self.values[i] {
+            Value::Vector(v) => std::array::from_fn(|k| v[k].clone().into_tree()),
+            _ => unreachable!("argument {i} is not a vector"),
+        }
+    }
+}
+
+/// Parses `args` against each signature in turn, returning the first match
+///
+/// If none of the signatures match, the error lists all accepted forms.
+pub(crate) fn parse_args(args: &[KValue], signatures: &[&[Param]]) -> runtime::Result<Args> {
+    for (form, params) in signatures.iter().enumerate() {
+        let values = match args {
+            [KValue::Map(map)] => parse_map(map, params),
+            _ => parse_positional(args, params)?,
+        };
+        if let Some(values) = values {
+            return Ok(Args { form, values });
+        }
+    }
+    unexpected_args(&describe(signatures), args)
+}
+
+/// Describes the accepted forms, e.g. `|radius: Number, [center: Vec3]|`
+fn describe(signatures: &[&[Param]]) -> String {
+    let forms = signatures
+        .iter()
+        .map(|params| {
+            let params = params
+                .iter()
+                .map(|p| match p.default {
+                    Some(_) => format!("[{}: {}]", p.name, p.type_name()),
+                    None => format!("{}: {}", p.name, p.type_name()),
+                })
+                .collect::<Vec<_>>();
+            format!("|{}|", params.join(", "))
+        })
+        .collect::<Vec<_>>();
+    let mut out = forms.join(" or ");
+    let keys = signatures
+        .iter()
+        .map(|params| {
+            let keys = params.iter().map(|p| p.name).collect::<Vec<_>>();
+            format!("{{{}}}", keys.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(" or ");
+    out.push_str(&format!(", or a single map {keys}"));
+    if signatures
+        .iter()
+        .any(|params| params.iter().any(|p| matches!(p.kind, Kind::Vector { .. })))
+    {
+        out.push_str("; vectors may be Vec2/Vec3, lists, tuples or numbers");
+    }
+    out
+}
+
+/// Parses positional arguments
+///
+/// A run of numbers for a vector parameter gives between 2 and all of its
+/// components, or a single one for `splat` parameters.  If the numbers could
+/// be split between parameters in more than one way, this is an error rather
+/// than a guess.
+fn parse_positional(args: &[KValue], params: &[Param]) -> runtime::Result<Option<Vec<Value>>> {
+    let Some((param, rest)) = params.split_first() else {
+        return Ok(args.is_empty().then(Vec::new));
+    };
+    let prepend = |value: Value, values: Option<Vec<Value>>| {
+        values.map(|mut values| {
+            values.insert(0, value);
+            values
+        })
+    };
+    let Some(arg) = args.first() else {
+        return Ok(match param.default_value() {
+            Some(value) => prepend(value, parse_positional(args, rest)?),
+            None => None,
+        });
+    };
+    let Kind::Vector {
+        len,
+        numeric,
+        splat,
+        pad,
+    } = param.kind
+    else {
+        return Ok(match single_value(arg, param.kind) {
+            Some(value) => prepend(value, parse_positional(&args[1..], rest)?),
+            None => None,
+        });
+    };
+    if let Some(v) = vector_components(arg) {
+        return Ok(match make_vector(v, len, numeric, splat, pad) {
+            Some(v) => prepend(Value::Vector(v), parse_positional(&args[1..], rest)?),
+            None => None,
+        });
+    }
+
+    // A run of numbers, trying every length which leaves a valid remainder
+    let run = args
+        .iter()
+        .take(len)
+        .map_while(Scalar::from_koto)
+        .collect::<Vec<_>>();
+    let mut found = None;
+    for n in (1..=run.len()).rev() {
+        if n == 1 && !splat {
+            continue;
+        }
+        let Some(v) = make_vector(run[..n].to_vec(), len, numeric, splat, pad) else {
+            continue;
+        };
+        let Some(values) = prepend(Value::Vector(v), parse_positional(&args[n..], rest)?) else {
+            continue;
+        };
+        if found.is_some() {
+            return runtime_error!(
+                "ambiguous numbers for `{}`, use a list or vector instead",
+                param.name
+            );
+        }
+        found = Some(values);
+    }
+    Ok(found)
+}
+
+fn parse_map(map: &KMap, params: &[Param]) -> Option<Vec<Value>> {
+    // Reject unknown keys, which are most likely typos
+    for key in map.data().keys() {
+        match key.value() {
+            KValue::Str(key) if params.iter().any(|p| p.name == key.as_str()) => (),
+            _ => return None,
+        }
+    }
+    params
+        .iter()
+        .map(|param| match map.get(param.name) {
+            Some(value) => match param.kind {
+                Kind::Vector {
+                    len,
+                    numeric,
+                    splat,
+                    pad,
+                } => {
+                    let v = match vector_components(&value) {
+                        Some(v) => v,
+                        // A single number is only accepted if it is copied to
+                        // all components, as in positional arguments
+                        None if splat => vec![Scalar::from_koto(&value)?],
+                        None => return None,
+                    };
+                    Some(Value::Vector(make_vector(v, len, numeric, splat, pad)?))
+                }
+                kind => single_value(&value, kind),
+            },
+            None => param.default_value(),
+        })
+        .collect()
+}
+
+fn single_value(value: &KValue, kind: Kind) -> Option<Value> {
+    match (kind, value) {
+        (Kind::Shape, KValue::Object(obj)) => {
+            maybe_tree(obj).map(|tree| Value::Shape(obj.clone(), tree))
+        }
+        (Kind::Number, KValue::Number(n)) => Some(Value::Scalar(Scalar::Number(f64::from(n)))),
+        (Kind::Field, value) => Scalar::from_koto(value).map(Value::Scalar),
+        (Kind::Str, KValue::Str(s)) => Some(Value::Str(s.to_string())),
+        (Kind::Points, value) => maybe_points(value).map(Value::Points),
+        _ => None,
+    }
+}
+
+/// Returns the components of a vector, list or tuple
+fn vector_components(value: &KValue) -> Option<Vec<Scalar>> {
+    match value {
+        KValue::Object(obj) if obj.is_a::<KVec2>() => Some(obj.cast::<KVec2>().ok()?.0.to_vec()),
+        KValue::Object(obj) if obj.is_a::<KVec3>() => Some(obj.cast::<KVec3>().ok()?.0.to_vec()),
+        KValue::List(list) => list.data().iter().map(Scalar::from_koto).collect(),
+        KValue::Tuple(tuple) => tuple.iter().map(Scalar::from_koto).collect(),
+        _ => None,
+    }
+}
+
+fn make_vector(
+    mut v: Vec<Scalar>,
+    len: usize,
+    numeric: bool,
+    splat: bool,
+    pad: f64,
+) -> Option<Vec<Scalar>> {
+    if v.is_empty() || v.len() > len || (numeric && v.iter().any(|s| s.as_number().is_none())) {
+        return None;
+    }
+    if splat && v.len() == 1 {
+        v.resize(len, v[0].clone());
+    } else {
+        v.resize(len, Scalar::Number(pad));
+    }
+    Some(v)
+}
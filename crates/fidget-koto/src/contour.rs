@@ -0,0 +1,434 @@
+//! Contours of drawn shapes, and an SVG writer for them
+//!
+//! Shapes are sliced at a fixed `z` and contoured with marching squares on a
+//! regular grid.  Regions of the grid are culled with interval arithmetic, so
+//! only cells near the surface are evaluated, and the segments are joined
+//! into closed polylines.  Outer boundaries run counter-clockwise and holes
+//! clockwise, in model coordinates.
+//!
+//! Shapes are clipped to the bounds, so every polyline is closed.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use fidget_koto::{Engine, contour};
+//!
+//! let out = Engine::default().run("draw circle 1")?;
+//! let settings = contour::Settings {
+//!     bounds: contour::Bounds { center: [0.0, 0.0], size: 1.5 },
+//!     ..Default::default()
+//! };
+//! let contours = out.contours(&settings)?;
+//! let mut file = std::fs::File::create("circle.svg")?;
+//! let svg = contour::SvgSettings {
+//!     bounds: settings.bounds,
+//!     units: contour::Units::Mm,
+//!     ..Default::default()
+//! };
+//! contour::write_svg(&contours, &svg, &mut file)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::DrawShape;
+use crate::utils::escape_xml;
+use fidget::{context::Tree, shape::EzShape, types::Interval, vm::VmShape};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Square region in the XY plane, as a center and half of its side length
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    /// Center of the square
+    pub center: [f32; 2],
+    /// Half of the side length of the square
+    pub size: f32,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            center: [0.0; 2],
+            size: 1.0,
+        }
+    }
+}
+
+/// Contouring settings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Region which is contoured
+    pub bounds: Bounds,
+    /// Number of grid cells along each side of the bounds
+    pub resolution: u32,
+    /// Height at which shapes are sliced
+    pub z: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bounds: Bounds::default(),
+            resolution: 256,
+            z: 0.0,
+        }
+    }
+}
+
+/// Contours of a drawn shape, with its color and name
+#[derive(Clone, Debug)]
+pub struct ShapeContours {
+    /// Closed polylines, without repeating the first point
+    pub polylines: Vec<Vec<[f32; 2]>>,
+    /// Color of the shape
+    pub color_rgb: [u8; 3],
+    /// Name of the shape
+    pub name: Option<String>,
+}
+
+/// Grid regions with at most this many cells per side are evaluated directly
+const LEAF_CELLS: u32 = 16;
+
+/// Edge between two grid samples: `(i, j, vertical)`
+///
+/// A horizontal edge joins samples `(i, j)` and `(i + 1, j)`, a vertical one
+/// joins `(i, j)` and `(i, j + 1)`.
+type Edge = (u32, u32, bool);
+
+/// Segments of a cell as `(from, to)` edge indices, for each corner pattern
+///
+/// Corners are numbered counter-clockwise from the bottom left and edges
+/// from the bottom (0: bottom, 1: right, 2: top, 3: left).  Bit `k` of the
+/// pattern is set if corner `k` is inside.  Segments keep the inside on their
+/// left.  The ambiguous patterns 5 and 10 are resolved by `center_inside`.
+fn cell_segments(pattern: u8, center_inside: bool) -> &'static [(usize, usize)] {
+    match (pattern, center_inside) {
+        (0 | 15, _) => &[],
+        (1, _) => &[(0, 3)],
+        (2, _) => &[(1, 0)],
+        (3, _) => &[(1, 3)],
+        (4, _) => &[(2, 1)],
+        (5, true) => &[(0, 1), (2, 3)],
+        (5, false) => &[(0, 3), (2, 1)],
+        (6, _) => &[(2, 0)],
+        (7, _) => &[(2, 3)],
+        (8, _) => &[(3, 2)],
+        (9, _) => &[(0, 2)],
+        (10, true) => &[(3, 0), (1, 2)],
+        (10, false) => &[(1, 0), (3, 2)],
+        (11, _) => &[(1, 2)],
+        (12, _) => &[(3, 1)],
+        (13, _) => &[(0, 1)],
+        (14, _) => &[(3, 0)],
+        _ => unreachable!("invalid corner pattern {pattern}"),
+    }
+}
+
+/// Interval evaluator over a box in the XY plane
+type IntervalFn<'a> = dyn FnMut([f32; 2], [f32; 2]) -> Result<Interval, fidget::Error> + 'a;
+
+/// Evaluator for many points in the XY plane
+type SliceFn<'a> = dyn FnMut(&[f32], &[f32]) -> Result<Vec<f32>, fidget::Error> + 'a;
+
+/// Marching squares over a grid of samples
+struct Contourer {
+    /// Position of the first sample
+    origin: [f32; 2],
+    /// Distance between samples
+    step: f32,
+    /// Position of the contour on each crossed edge
+    vertices: HashMap<Edge, [f32; 2]>,
+    /// Next edge along the contour, sorted so that the output is deterministic
+    next: BTreeMap<Edge, Edge>,
+}
+
+impl Contourer {
+    fn position(&self, i: u32, j: u32) -> [f32; 2] {
+        [
+            self.origin[0] + i as f32 * self.step,
+            self.origin[1] + j as f32 * self.step,
+        ]
+    }
+
+    /// Contours the cells `[i0, i1) x [j0, j1)`
+    fn region(
+        &mut self,
+        [i0, i1, j0, j1]: [u32; 4],
+        interval: &mut IntervalFn,
+        slice: &mut SliceFn,
+    ) -> Result<(), fidget::Error> {
+        let [x0, y0] = self.position(i0, j0);
+        let [x1, y1] = self.position(i1, j1);
+        let value = interval([x0, x1], [y0, y1])?;
+        // The surface can't cross a region which is entirely inside or outside
+        if value.lower() > 0.0 || value.upper() < 0.0 {
+            return Ok(());
+        }
+
+        let (w, h) = (i1 - i0, j1 - j0);
+        if w <= LEAF_CELLS && h <= LEAF_CELLS {
+            return self.leaf([i0, i1, j0, j1], slice);
+        }
+        if w >= h {
+            let mid = i0 + w / 2;
+            self.region([i0, mid, j0, j1], interval, slice)?;
+            self.region([mid, i1, j0, j1], interval, slice)
+        } else {
+            let mid = j0 + h / 2;
+            self.region([i0, i1, j0, mid], interval, slice)?;
+            self.region([i0, i1, mid, j1], interval, slice)
+        }
+    }
+
+    /// Evaluates every sample of a small region and contours its cells
+    fn leaf(
+        &mut self,
+        [i0, i1, j0, j1]: [u32; 4],
+        slice: &mut SliceFn,
+    ) -> Result<(), fidget::Error> {
+        let stride = (i1 - i0 + 1) as usize;
+        let (mut xs, mut ys) = (vec![], vec![]);
+        for j in j0..=j1 {
+            for i in i0..=i1 {
+                let [x, y] = self.position(i, j);
+                xs.push(x);
+                ys.push(y);
+            }
+        }
+        let values = slice(&xs, &ys)?;
+        let value = |i: u32, j: u32| values[(j - j0) as usize * stride + (i - i0) as usize];
+
+        for j in j0..j1 {
+            for i in i0..i1 {
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let v = corners.map(|(ci, cj)| value(ci, cj));
+                let pattern = (0..4)
+                    .filter(|k| v[*k] < 0.0)
+                    .fold(0u8, |acc, k| acc | (1 << k));
+                let center_inside = v.iter().sum::<f32>() < 0.0;
+                let edges: [Edge; 4] = [
+                    (i, j, false),
+                    (i + 1, j, true),
+                    (i, j + 1, false),
+                    (i, j, true),
+                ];
+                for &(from, to) in cell_segments(pattern, center_inside) {
+                    for k in [from, to] {
+                        // Corners at both ends of edge `k`
+                        let (a, b) = (k, (k + 1) % 4);
+                        let t = v[a] / (v[a] - v[b]);
+                        let [xa, ya] = self.position(corners[a].0, corners[a].1);
+                        let [xb, yb] = self.position(corners[b].0, corners[b].1);
+                        self.vertices
+                            .entry(edges[k])
+                            .or_insert([xa + (xb - xa) * t, ya + (yb - ya) * t]);
+                    }
+                    self.next.insert(edges[from], edges[to]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Joins the segments into polylines
+    fn polylines(mut self) -> Vec<Vec<[f32; 2]>> {
+        let mut out = vec![];
+        while let Some(&start) = self.next.keys().next() {
+            let mut line = vec![];
+            let mut edge = start;
+            while let Some(next) = self.next.remove(&edge) {
+                line.push(self.vertices[&edge]);
+                edge = next;
+            }
+            out.push(line);
+        }
+        out
+    }
+}
+
+/// Contours a tree in the XY plane
+///
+/// The tree is clipped to the bounds, then sampled on a grid which extends
+/// one cell past them, so that every polyline is closed.
+pub fn contour_tree(tree: &Tree, settings: &Settings) -> Result<Vec<Vec<[f32; 2]>>, fidget::Error> {
+    let Bounds { center, size } = settings.bounds;
+    let [cx, cy] = center;
+    let clip = (Tree::x() - cx as f64)
+        .abs()
+        .max((Tree::y() - cy as f64).abs())
+        - size as f64;
+    let shape = VmShape::from(tree.max(clip));
+    let z = settings.z;
+
+    let mut interval_eval = VmShape::new_interval_eval();
+    let interval_tape = shape.ez_interval_tape();
+    let mut interval =
+        |[x0, x1]: [f32; 2], [y0, y1]: [f32; 2]| -> Result<Interval, fidget::Error> {
+            let (value, _trace) = interval_eval.eval(
+                &interval_tape,
+                Interval::new(x0, x1),
+                Interval::new(y0, y1),
+                Interval::new(z, z),
+            )?;
+            Ok(value)
+        };
+    let mut slice_eval = VmShape::new_float_slice_eval();
+    let slice_tape = shape.ez_float_slice_tape();
+    let mut slice = |xs: &[f32], ys: &[f32]| -> Result<Vec<f32>, fidget::Error> {
+        let zs = vec![z; xs.len()];
+        Ok(slice_eval.eval(&slice_tape, xs, ys, &zs)?.to_vec())
+    };
+
+    let resolution = settings.resolution.max(1);
+    let step = 2.0 * size / resolution as f32;
+    let mut contourer = Contourer {
+        origin: [cx - size - step, cy - size - step],
+        step,
+        vertices: HashMap::new(),
+        next: BTreeMap::new(),
+    };
+    let cells = resolution + 2;
+    contourer.region([0, cells, 0, cells], &mut interval, &mut slice)?;
+    Ok(contourer.polylines())
+}
+
+/// Contours a drawn shape
+///
+/// Variables other than `x`, `y` and `z` are replaced by their defaults.
+pub fn contour_shape(
+    shape: &DrawShape,
+    settings: &Settings,
+) -> Result<ShapeContours, fidget::Error> {
+    Ok(ShapeContours {
+        polylines: contour_tree(&shape.bind_defaults()?, settings)?,
+        color_rgb: shape.color_rgb,
+        name: shape.name.clone(),
+    })
+}
+
+/// Physical unit of one model unit in SVG output
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Units {
+    /// Millimeters, the usual unit for laser cutters
+    #[default]
+    Mm,
+    /// Centimeters
+    Cm,
+    /// Inches
+    In,
+    /// Points, 1/72 of an inch
+    Pt,
+    /// CSS pixels, 1/96 of an inch
+    Px,
+}
+
+impl Units {
+    /// Every unit
+    pub const ALL: [Self; 5] = [Self::Mm, Self::Cm, Self::In, Self::Pt, Self::Px];
+
+    /// Returns the SVG suffix of the unit, as parsed by [`FromStr`]
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Mm => "mm",
+            Self::Cm => "cm",
+            Self::In => "in",
+            Self::Pt => "pt",
+            Self::Px => "px",
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.suffix())
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|u| u.suffix() == s)
+            .ok_or_else(|| format!("unknown unit '{s}'"))
+    }
+}
+
+/// How contours are drawn in SVG output
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Style {
+    /// Outlines in the shape's color, with a width in model units
+    Stroke(f32),
+    /// Filled in the shape's color, with holes left empty
+    Fill,
+}
+
+/// SVG output settings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvgSettings {
+    /// Region of the model which is shown
+    pub bounds: Bounds,
+    /// Physical size of one model unit
+    pub units: Units,
+    /// How contours are drawn
+    pub style: Style,
+}
+
+impl Default for SvgSettings {
+    fn default() -> Self {
+        Self {
+            bounds: Bounds::default(),
+            units: Units::default(),
+            style: Style::Stroke(0.01),
+        }
+    }
+}
+
+/// Writes contours as an SVG file, with one path per shape
+///
+/// Every path uses its shape's color, and named shapes are labelled with a
+/// `<title>`.  The Y axis points up in the model and down in SVG, so the
+/// picture is flipped to keep it upright.
+pub fn write_svg(
+    contours: &[ShapeContours],
+    settings: &SvgSettings,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let Bounds { center, size } = settings.bounds;
+    let unit = settings.units.suffix();
+    let side = 2.0 * size;
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{side}{unit}" height="{side}{unit}" viewBox="{} {} {side} {side}">"#,
+        center[0] - size,
+        -center[1] - size,
+    )?;
+    for c in contours {
+        if c.polylines.is_empty() {
+            continue;
+        }
+        let [r, g, b] = c.color_rgb;
+        let color = format!("#{r:02x}{g:02x}{b:02x}");
+        let style = match settings.style {
+            Style::Stroke(width) => {
+                format!(r#"fill="none" stroke="{color}" stroke-width="{width}""#)
+            }
+            Style::Fill => format!(r#"fill="{color}" fill-rule="evenodd" stroke="none""#),
+        };
+        write!(out, r#"  <path {style} d=""#)?;
+        for line in &c.polylines {
+            for (i, [x, y]) in line.iter().enumerate() {
+                let cmd = if i == 0 { 'M' } else { 'L' };
+                write!(out, "{cmd}{x} {} ", -y)?;
+            }
+            write!(out, "Z ")?;
+        }
+        match &c.name {
+            Some(name) => writeln!(out, r#""><title>{}</title></path>"#, escape_xml(name))?,
+            None => writeln!(out, r#""/>"#)?,
+        }
+    }
+    writeln!(out, "</svg>")
+}
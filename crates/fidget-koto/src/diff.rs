@@ -0,0 +1,297 @@
+//! Symbolic differentiation of [`Tree`]s
+//!
+//! Trees are imported into a [`Context`], which resolves remapped axes and
+//! deduplicates shared subtrees, then differentiated node by node.  Terms
+//! which are known to be zero are dropped as the derivative is built, so the
+//! result stays reasonably small.
+//!
+//! Piecewise operations (`min`, `max`, `abs`, `floor` and friends) are
+//! differentiated piecewise; at the seams, `min` and `max` average the
+//! derivatives of both sides.
+//!
+//! In Koto:
+//!
+//! ```koto
+//! f = x.square() + y * z
+//! dfdx = f.deriv x           # 2 * x
+//! g = f.grad()               # Vec3 of Trees
+//! draw normalize_sdf lattice.gyroid(1, 0.1)
+//! ```
+
+use crate::inspect::post_order;
+use fidget::{
+    Error,
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use std::collections::HashMap;
+
+/// Builds derivatives of nodes in a context with respect to one variable
+///
+/// `None` stands for a derivative which is known to be zero.
+struct Differentiator<'a> {
+    ctx: &'a mut Context,
+    var: Var,
+    memo: HashMap<Node, Option<Node>>,
+}
+
+impl<'a> Differentiator<'a> {
+    fn new(ctx: &'a mut Context, var: Var) -> Self {
+        Self {
+            ctx,
+            var,
+            memo: HashMap::new(),
+        }
+    }
+
+    fn deriv(&mut self, root: Node) -> Result<Option<Node>, Error> {
+        for node in post_order(self.ctx, root) {
+            if self.memo.contains_key(&node) {
+                continue;
+            }
+            let op = self.ctx.get_op(node).ok_or(Error::BadNode)?.clone();
+            let d = match op {
+                Op::Input(v) => (v == self.var).then(|| self.ctx.constant(1.0)),
+                Op::Const(_) => None,
+                Op::Unary(opcode, a) => match self.memo[&a] {
+                    Some(da) => self.unary(opcode, node, a, da)?,
+                    None => None,
+                },
+                Op::Binary(opcode, a, b) => match (self.memo[&a], self.memo[&b]) {
+                    (None, None) => None,
+                    (da, db) => self.binary(opcode, node, a, b, da, db)?,
+                },
+            };
+            self.memo.insert(node, d);
+        }
+        Ok(self.memo[&root])
+    }
+
+    /// Derivative of `node = op(a)`, given `da`
+    fn unary(
+        &mut self,
+        opcode: UnaryOpcode,
+        node: Node,
+        a: Node,
+        da: Node,
+    ) -> Result<Option<Node>, Error> {
+        let ctx = &mut *self.ctx;
+        let one = ctx.constant(1.0);
+        let d = match opcode {
+            UnaryOpcode::Neg => ctx.neg(da)?,
+            UnaryOpcode::Abs => {
+                let zero = ctx.constant(0.0);
+                let sign = ctx.compare(a, zero)?;
+                ctx.mul(sign, da)?
+            }
+            UnaryOpcode::Recip => {
+                let a2 = ctx.square(a)?;
+                let q = ctx.div(da, a2)?;
+                ctx.neg(q)?
+            }
+            UnaryOpcode::Sqrt => {
+                let two = ctx.constant(2.0);
+                let den = ctx.mul(node, two)?;
+                ctx.div(da, den)?
+            }
+            UnaryOpcode::Square => {
+                let two = ctx.constant(2.0);
+                let k = ctx.mul(a, two)?;
+                ctx.mul(k, da)?
+            }
+            UnaryOpcode::Sin => {
+                let cos = ctx.cos(a)?;
+                ctx.mul(cos, da)?
+            }
+            UnaryOpcode::Cos => {
+                let sin = ctx.sin(a)?;
+                let d = ctx.mul(sin, da)?;
+                ctx.neg(d)?
+            }
+            UnaryOpcode::Tan => {
+                let cos = ctx.cos(a)?;
+                let cos2 = ctx.square(cos)?;
+                ctx.div(da, cos2)?
+            }
+            UnaryOpcode::Asin | UnaryOpcode::Acos => {
+                let a2 = ctx.square(a)?;
+                let r = ctx.sub(one, a2)?;
+                let den = ctx.sqrt(r)?;
+                let d = ctx.div(da, den)?;
+                if opcode == UnaryOpcode::Acos {
+                    ctx.neg(d)?
+                } else {
+                    d
+                }
+            }
+            UnaryOpcode::Atan => {
+                let a2 = ctx.square(a)?;
+                let den = ctx.add(one, a2)?;
+                ctx.div(da, den)?
+            }
+            UnaryOpcode::Exp => ctx.mul(node, da)?,
+            UnaryOpcode::Ln => ctx.div(da, a)?,
+            // Piecewise constant
+            UnaryOpcode::Floor | UnaryOpcode::Ceil | UnaryOpcode::Round | UnaryOpcode::Not => {
+                return Ok(None);
+            }
+        };
+        Ok(Some(d))
+    }
+
+    /// Derivative of `node = op(a, b)`, given `da` and `db`
+    fn binary(
+        &mut self,
+        opcode: BinaryOpcode,
+        node: Node,
+        a: Node,
+        b: Node,
+        da: Option<Node>,
+        db: Option<Node>,
+    ) -> Result<Option<Node>, Error> {
+        match opcode {
+            BinaryOpcode::Add => self.sum(da, db),
+            BinaryOpcode::Sub => self.difference(da, db),
+            BinaryOpcode::Mul => {
+                let t1 = self.scale(b, da)?;
+                let t2 = self.scale(a, db)?;
+                self.sum(t1, t2)
+            }
+            BinaryOpcode::Div => {
+                // (a / b)' = da / b - (a / b) * db / b
+                let t1 = self.map(da, |ctx, da| ctx.div(da, b))?;
+                let t2 = self.map(db, |ctx, db| {
+                    let n = ctx.mul(node, db)?;
+                    ctx.div(n, b)
+                })?;
+                self.difference(t1, t2)
+            }
+            BinaryOpcode::Atan => {
+                // atan2(a, b)' = (b * da - a * db) / (a² + b²)
+                let t1 = self.scale(b, da)?;
+                let t2 = self.scale(a, db)?;
+                let num = self.difference(t1, t2)?;
+                self.map(num, |ctx, num| {
+                    let a2 = ctx.square(a)?;
+                    let b2 = ctx.square(b)?;
+                    let den = ctx.add(a2, b2)?;
+                    ctx.div(num, den)
+                })
+            }
+            BinaryOpcode::Min | BinaryOpcode::Max => {
+                // compare returns -1, 0 or 1, so the weights are 0, 0.5 or 1
+                let ctx = &mut *self.ctx;
+                let c = ctx.compare(a, b)?;
+                let half = ctx.constant(0.5);
+                let one = ctx.constant(1.0);
+                let below = ctx.sub(one, c)?;
+                let below = ctx.mul(below, half)?;
+                let above = ctx.add(one, c)?;
+                let above = ctx.mul(above, half)?;
+                let (wa, wb) = if opcode == BinaryOpcode::Min {
+                    (below, above)
+                } else {
+                    (above, below)
+                };
+                let t1 = self.scale(wa, da)?;
+                let t2 = self.scale(wb, db)?;
+                self.sum(t1, t2)
+            }
+            BinaryOpcode::Compare => Ok(None),
+            BinaryOpcode::Mod => {
+                // a mod b = a - b * floor(a / b)
+                let q = self.ctx.div(a, b)?;
+                let q = self.ctx.floor(q)?;
+                let t2 = self.scale(q, db)?;
+                self.difference(da, t2)
+            }
+            BinaryOpcode::And | BinaryOpcode::Or => {
+                // `and` picks `a` if it is zero, else `b`; `or` picks `a` if
+                // it is non-zero, else `b`
+                let not_a = self.ctx.not(a)?;
+                let (pick_a, pick_b) = if opcode == BinaryOpcode::And {
+                    (not_a, a)
+                } else {
+                    (a, not_a)
+                };
+                let t1 = self.map(da, |ctx, da| ctx.and(pick_a, da))?;
+                let t2 = self.map(db, |ctx, db| ctx.and(pick_b, db))?;
+                self.sum(t1, t2)
+            }
+        }
+    }
+
+    fn map(
+        &mut self,
+        d: Option<Node>,
+        f: impl FnOnce(&mut Context, Node) -> Result<Node, Error>,
+    ) -> Result<Option<Node>, Error> {
+        d.map(|d| f(self.ctx, d)).transpose()
+    }
+
+    fn scale(&mut self, k: Node, d: Option<Node>) -> Result<Option<Node>, Error> {
+        self.map(d, |ctx, d| ctx.mul(k, d))
+    }
+
+    fn sum(&mut self, a: Option<Node>, b: Option<Node>) -> Result<Option<Node>, Error> {
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Some(self.ctx.add(a, b)?)),
+            (a, b) => Ok(a.or(b)),
+        }
+    }
+
+    fn difference(&mut self, a: Option<Node>, b: Option<Node>) -> Result<Option<Node>, Error> {
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Some(self.ctx.sub(a, b)?)),
+            (Some(a), None) => Ok(Some(a)),
+            (None, b) => self.map(b, |ctx, b| ctx.neg(b)),
+        }
+    }
+}
+
+fn export(ctx: &Context, d: Option<Node>) -> Result<Tree, Error> {
+    match d {
+        Some(d) => ctx.export(d),
+        None => Ok(Tree::constant(0.0)),
+    }
+}
+
+/// Returns the variable if the tree is a single input, e.g. `x`
+pub fn as_var(tree: &Tree) -> Option<Var> {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+    match ctx.get_op(root)? {
+        Op::Input(var) => Some(*var),
+        _ => None,
+    }
+}
+
+/// Builds the partial derivative of `tree` with respect to `var`
+pub fn deriv(tree: &Tree, var: Var) -> Result<Tree, Error> {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+    let d = Differentiator::new(&mut ctx, var).deriv(root)?;
+    export(&ctx, d)
+}
+
+/// Builds the gradient of `tree`, as partial derivatives along `x`, `y`, `z`
+pub fn grad(tree: &Tree) -> Result<[Tree; 3], Error> {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+    let dx = Differentiator::new(&mut ctx, Var::X).deriv(root)?;
+    let dy = Differentiator::new(&mut ctx, Var::Y).deriv(root)?;
+    let dz = Differentiator::new(&mut ctx, Var::Z).deriv(root)?;
+    Ok([export(&ctx, dx)?, export(&ctx, dy)?, export(&ctx, dz)?])
+}
+
+/// Divides `tree` by the length of its gradient
+///
+/// Near the surface, the result approximates a distance field with a
+/// gradient length of 1, even if `tree` is not a distance field.  Far from
+/// the surface it is only a rough estimate.  The gradient length is clamped
+/// to at least `epsilon`, to avoid dividing by zero at critical points.
+pub fn normalize_sdf(tree: &Tree, epsilon: f64) -> Result<Tree, Error> {
+    let [dx, dy, dz] = grad(tree)?;
+    let length = (dx.square() + dy.square() + dz.square()).sqrt();
+    Ok(tree.clone() / length.max(epsilon))
+}
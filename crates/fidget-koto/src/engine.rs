@@ -1,10 +1,25 @@
 use super::{
     DrawShape, KCircle, KDifference, KIntersection, KInverse, KMove, KScale, KSphere, KTree,
-    KUnion, ScriptContext,
+    KUnion, NamedVar, ScriptContext,
 };
-use crate::utils::maybe_tree;
-use fidget::context::Tree;
-use koto::{prelude::*, runtime};
+use crate::args::{Param, parse_args};
+use crate::diff::normalize_sdf;
+use crate::inspect;
+use crate::lattice::add_lattice_fns;
+use crate::math::ShaderMath;
+use crate::noise::add_noise_fns;
+use crate::rhai::add_rhai_fns;
+use crate::shapes::{
+    Axis, SCALE, TRANSLATION, bend, displace, displace_xyz, offset, onion, polar_array, repeat,
+    repeat_limited, round_edges, shell, taper, twist,
+};
+use crate::sweep::add_sweep_fns;
+use crate::utils::{maybe_tree, maybe_tree_or_number};
+use crate::vec::{add_vec_fns, flatten_vectors};
+use crate::view::{Projection, ViewHints};
+use fidget::{context::Tree, var::Var};
+use koto::{prelude::*, runtime, runtime::runtime_error};
+use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -12,6 +27,60 @@ use std::time::Duration;
 pub struct EngineSettings {
     add_fidget_fns: bool,
     execution_limit: Duration,
+    optimize: bool,
+    rhai_compat: bool,
+    vars: Vec<(String, f64)>,
+}
+
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self {
+            add_fidget_fns: false,
+            execution_limit: Duration::from_secs(1),
+            optimize: true,
+            rhai_compat: false,
+            vars: vec![],
+        }
+    }
+}
+
+impl EngineSettings {
+    /// Makes all `fidget` helper functions available at the top level
+    pub fn with_add_fidget_fns(mut self, add_fidget_fns: bool) -> Self {
+        self.add_fidget_fns = add_fidget_fns;
+        self
+    }
+
+    /// Sets the time limit for script execution
+    pub fn with_execution_limit(mut self, limit: Duration) -> Self {
+        self.execution_limit = limit;
+        self
+    }
+
+    /// Enables or disables optimization of drawn shapes
+    pub fn with_optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Adds `draw_rgb` and Rhai-style builtins, see [`rhai`](crate::rhai)
+    ///
+    /// Builtins with the same name but a different argument order, such as
+    /// `circle` and `sphere`, are replaced by their Rhai versions.
+    pub fn with_rhai_compat(mut self, rhai_compat: bool) -> Self {
+        self.rhai_compat = rhai_compat;
+        self
+    }
+
+    /// Declares a variable which scripts can use by name, like `x`, `y`, `z`
+    ///
+    /// The default is used if the host doesn't bind a value; scripts can't
+    /// override it.  Declaring the same name twice replaces the default.
+    pub fn with_var(mut self, name: &str, default: f64) -> Self {
+        self.vars.retain(|(n, _)| n != name);
+        self.vars.push((name.to_owned(), default));
+        self
+    }
 }
 
 /// Engine for evaluating a Koto script with Fidget-specific bindings
@@ -19,14 +88,12 @@ pub struct Engine {
     settings: EngineSettings,
     engine: Koto,
     context: Arc<Mutex<ScriptContext>>,
+    vars: Arc<Mutex<Vec<NamedVar>>>,
 }
 
 impl Default for Engine {
     fn default() -> Self {
-        Self::new(EngineSettings {
-            add_fidget_fns: false,
-            execution_limit: Duration::from_secs(1),
-        })
+        Self::new(EngineSettings::default())
     }
 }
 
@@ -65,192 +132,359 @@ impl Engine {
             prelude.insert("fidget", module);
         }
 
+        let module = KMap::with_type("lattice");
+        add_lattice_fns(&module);
+        prelude.insert("lattice", module);
+
+        add_noise_fns(&prelude);
+        add_sweep_fns(&prelude);
+        add_vec_fns(&prelude);
+
         let context = Arc::new(Mutex::new(ScriptContext::new()));
 
-        let context_clone = context.clone();
-        prelude.add_fn("draw", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Object(obj)] => {
-                    if let Some(tree) = maybe_tree(&obj) {
-                        context_clone.lock().unwrap().shapes.push(DrawShape {
-                            tree,
-                            color_rgb: [u8::MAX; 3],
-                        });
-                        Ok(KValue::Null)
-                    } else {
-                        unexpected_args("|Tree|", &args)
-                    }
-                }
-                [
-                    KValue::Object(obj),
-                    KValue::Number(r),
-                    KValue::Number(g),
-                    KValue::Number(b),
-                ] => {
-                    if let Some(tree) = maybe_tree(&obj) {
-                        context_clone.lock().unwrap().shapes.push(DrawShape {
-                            tree,
-                            color_rgb: [to_u8(r), to_u8(g), to_u8(b)],
-                        });
-                        Ok(KValue::Null)
-                    } else {
-                        unexpected_args("|Tree|", &args)
+        let vars = Arc::new(Mutex::new(
+            settings
+                .vars
+                .iter()
+                .map(|(name, default)| new_var(name, *default))
+                .collect::<Vec<_>>(),
+        ));
+
+        let vars_clone = vars.clone();
+        let host_vars = settings
+            .vars
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        prelude.add_fn("var", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::string("name"),
+                    Param::number("default").or_number(0.0),
+                ]],
+            )?;
+            let (name, default) = (args.string(0), args.number(1));
+            let var = match name {
+                "x" => Var::X,
+                "y" => Var::Y,
+                "z" => Var::Z,
+                _ => {
+                    let mut vars = vars_clone.lock().unwrap();
+                    match vars.iter_mut().find(|v| v.name == name) {
+                        Some(v) => {
+                            // Host declarations take precedence over scripts
+                            if !host_vars.iter().any(|n| n == name) {
+                                v.default = default;
+                            }
+                            v.var
+                        }
+                        None => {
+                            let v = new_var(name, default);
+                            vars.push(v.clone());
+                            v.var
+                        }
                     }
                 }
-                unexpected => unexpected_args("|Tree|", &unexpected),
-            }
+            };
+            Ok(KTree::from(Tree::from(var)).into())
         });
 
-        prelude.add_fn("circle", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Number(radius)] => {
-                    let result = KCircle::new(f64::from(radius), f64::from(0.0), f64::from(0.0));
-                    Ok(KValue::Object(KObject::from(result)))
-                }
-                [KValue::Number(radius), KValue::Number(x), KValue::Number(y)] => {
-                    let result = KCircle::new(f64::from(radius), f64::from(x), f64::from(y));
-                    Ok(KValue::Object(KObject::from(result)))
-                }
-                unexpected => unexpected_args("|Circle|", &unexpected),
-            }
+        let context_clone = context.clone();
+        prelude.add_fn("draw", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::shape("shape"),
+                    Param::vec3("color").splat().or_vector([1.0; 3]),
+                    Param::string("name").optional(),
+                ]],
+            )?;
+            context_clone.lock().unwrap().shapes.push(DrawShape {
+                tree: args.tree(0),
+                color_rgb: args.vec3(1).map(to_u8),
+                name: args.opt_string(2).map(str::to_owned),
+                vars: vec![],
+            });
+            Ok(KValue::Null)
         });
 
-        prelude.add_fn("sphere", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Number(radius)] => {
-                    let result = KSphere::new(
-                        f64::from(radius),
-                        f64::from(0.0),
-                        f64::from(0.0),
-                        f64::from(0.0),
+        let context_clone = context.clone();
+        prelude.add_fn("view", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::number("scale").optional(),
+                    Param::number("pitch").optional(),
+                    Param::number("yaw").optional(),
+                    Param::number("roll").optional(),
+                    Param::vec3("center").optional(),
+                    Param::string("projection").optional(),
+                    Param::number("perspective").optional(),
+                    Param::number("zflatten").optional(),
+                ]],
+            )?;
+            let bad_scale = |s: &f64| !(*s > 0.0 && s.is_finite());
+            if let Some(scale) = args.opt_number(0).filter(bad_scale) {
+                return runtime_error!("scale must be positive, got {scale}");
+            }
+            // A strength on its own implies a perspective projection
+            let projection = match (args.opt_string(5), args.opt_number(6)) {
+                (None, None) => None,
+                (Some("isometric"), None) => Some(Projection::Isometric),
+                (Some("perspective") | None, Some(p)) => Some(Projection::Perspective(p)),
+                (Some("isometric"), Some(_)) => {
+                    return runtime_error!("an isometric projection has no `perspective` strength");
+                }
+                (Some("perspective"), None) => {
+                    return runtime_error!(
+                        "a perspective projection needs a `perspective` strength"
                     );
-                    Ok(KValue::Object(KObject::from(result)))
                 }
-                [
-                    KValue::Number(radius),
-                    KValue::Number(x),
-                    KValue::Number(y),
-                    KValue::Number(z),
-                ] => {
-                    let result =
-                        KSphere::new(f64::from(radius), f64::from(x), f64::from(y), f64::from(z));
-                    Ok(KValue::Object(KObject::from(result)))
+                (Some(p), _) => {
+                    return runtime_error!(
+                        "invalid projection '{p}', expected 'isometric' or 'perspective'"
+                    );
                 }
-                unexpected => unexpected_args("|Sphere|", &unexpected),
-            }
+            };
+            context_clone.lock().unwrap().view.merge(&ViewHints {
+                scale: args.opt_number(0),
+                pitch: args.opt_number(1),
+                yaw: args.opt_number(2),
+                roll: args.opt_number(3),
+                center: args.opt_vec3(4),
+                projection,
+                zflatten: args.opt_number(7),
+            });
+            Ok(KValue::Null)
         });
 
-        prelude.add_fn("union", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Object(obj_a), KValue::Object(obj_b)] => {
-                    if let (Some(tree_a), Some(tree_b)) = (maybe_tree(obj_a), maybe_tree(obj_b)) {
-                        let result = KUnion::new(tree_a, tree_b);
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Union|", &args)
-                    }
-                }
-                unexpected => unexpected_args("|Union|", &unexpected),
-            }
+        prelude.add_fn("circle", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::number("radius"),
+                    Param::vec2("center").or_vector([0.0; 3]),
+                ]],
+            )?;
+            let [x, y] = args.vec2(1);
+            Ok(KValue::Object(KObject::from(KCircle::new(
+                args.number(0),
+                x,
+                y,
+            ))))
         });
 
-        prelude.add_fn("intersection", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Object(obj_a), KValue::Object(obj_b)] => {
-                    if let (Some(tree_a), Some(tree_b)) = (maybe_tree(obj_a), maybe_tree(obj_b)) {
-                        let result = KIntersection::new(tree_a, tree_b);
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Intersection|", &args)
-                    }
-                }
-                unexpected => unexpected_args("|Intersection|", &unexpected),
-            }
+        prelude.add_fn("sphere", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::number("radius"),
+                    Param::vec3("center").or_vector([0.0; 3]),
+                ]],
+            )?;
+            let [x, y, z] = args.vec3(1);
+            Ok(KValue::Object(KObject::from(KSphere::new(
+                args.number(0),
+                x,
+                y,
+                z,
+            ))))
         });
 
+        macro_rules! add_csg_fn {
+            ($name_string:literal, $kind:ident) => {
+                prelude.add_fn($name_string, move |ctx| {
+                    let args = parse_args(ctx.args(), &[&[Param::shape("a"), Param::shape("b")]])?;
+                    // Both arguments are shapes, so this always succeeds
+                    let result = $kind::from_inputs(vec![args.object(0), args.object(1)]);
+                    Ok(KValue::Object(result.unwrap()))
+                });
+            };
+        }
+
+        add_csg_fn!("union", KUnion);
+        add_csg_fn!("intersection", KIntersection);
+
         prelude.add_fn("difference", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Object(obj_a), KValue::Object(obj_b)] => {
-                    if let (Some(tree_a), Some(tree_b)) = (maybe_tree(obj_a), maybe_tree(obj_b)) {
-                        let result = KDifference::new(tree_a, tree_b);
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Difference|", &args)
-                    }
-                }
-                unexpected => unexpected_args("|Difference|", &unexpected),
-            }
+            let args = parse_args(ctx.args(), &[&[Param::shape("a"), Param::shape("b")]])?;
+            // Both arguments are shapes, so this always succeeds
+            let result = KDifference::from_inputs(args.object(0), args.object(1));
+            Ok(KValue::Object(result.unwrap()))
         });
 
         prelude.add_fn("inverse", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [KValue::Object(obj)] => {
-                    if let Some(tree) = maybe_tree(obj) {
-                        let result = KInverse::new(tree);
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Inverse|", &args)
-                    }
-                }
-                unexpected => unexpected_args("|Inverse|", &unexpected),
-            }
+            let args = parse_args(ctx.args(), &[&[Param::shape("shape")]])?;
+            // The argument is a shape, so this always succeeds
+            Ok(KValue::Object(
+                KInverse::from_input(args.object(0)).unwrap(),
+            ))
         });
 
         prelude.add_fn("move", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [
-                    KValue::Object(obj),
-                    KValue::Number(x),
-                    KValue::Number(y),
-                    KValue::Number(z),
-                ] => {
-                    if let Some(tree) = maybe_tree(obj) {
-                        let result = KMove::new(tree, f64::from(x), f64::from(y), f64::from(z));
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Move|", &args)
-                    }
-                }
-                unexpected => unexpected_args("|Move|", &unexpected),
-            }
+            let args = parse_args(ctx.args(), &[&[Param::shape("shape"), TRANSLATION]])?;
+            let [x, y, z] = args.vec3(1);
+            Ok(KValue::Object(KObject::from(KMove::new(
+                args.tree(0),
+                x,
+                y,
+                z,
+            ))))
         });
 
         prelude.add_fn("scale", move |ctx| {
-            let args = ctx.args();
-            match args {
-                [
-                    KValue::Object(obj),
-                    KValue::Number(x),
-                    KValue::Number(y),
-                    KValue::Number(z),
-                ] => {
-                    if let Some(tree) = maybe_tree(obj) {
-                        let result = KScale::new(tree, f64::from(x), f64::from(y), f64::from(z));
-                        Ok(KValue::Object(KObject::from(result)))
-                    } else {
-                        unexpected_args("|Scale|", &args)
-                    }
+            let args = parse_args(ctx.args(), &[&[Param::shape("shape"), SCALE]])?;
+            let [x, y, z] = args.vec3(1);
+            Ok(KValue::Object(KObject::from(KScale::new(
+                args.tree(0),
+                x,
+                y,
+                z,
+            ))))
+        });
+
+        prelude.add_fn("repeat", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[Param::shape("shape"), Param::vec3("spacing").splat()]],
+            )?;
+            Ok(KTree::from(repeat(args.tree(0), args.vec3(1))).into())
+        });
+
+        prelude.add_fn("repeat_limited", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::shape("shape"),
+                    Param::vec3("spacing").splat(),
+                    Param::vec3("count").splat().pad(1.0),
+                ]],
+            )?;
+            let result = repeat_limited(args.tree(0), args.vec3(1), args.vec3(2));
+            Ok(KTree::from(result).into())
+        });
+
+        prelude.add_fn("polar_array", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[Param::shape("shape"), Param::number("count")]],
+            )?;
+            Ok(KTree::from(polar_array(args.tree(0), args.number(1))).into())
+        });
+
+        prelude.add_fn("twist", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::shape("shape"),
+                    Param::number("rate"),
+                    Param::string("axis").or_str("z"),
+                ]],
+            )?;
+            match Axis::from_name(args.string(2)) {
+                Some(axis) => Ok(KTree::from(twist(args.tree(0), args.number(1), axis)).into()),
+                None => unexpected_args("|Tree, Number, [\"x\"|\"y\"|\"z\"]|", ctx.args()),
+            }
+        });
+
+        macro_rules! add_number_fn {
+            ($name_string:literal, $name:ident, $param:literal) => {
+                prelude.add_fn($name_string, move |ctx| {
+                    let args = parse_args(
+                        ctx.args(),
+                        &[&[Param::shape("shape"), Param::number($param)]],
+                    )?;
+                    Ok(KTree::from($name(args.tree(0), args.number(1))).into())
+                });
+            };
+        }
+
+        add_number_fn!("bend", bend, "k");
+        add_number_fn!("taper", taper, "factor");
+        add_number_fn!("offset", offset, "d");
+        add_number_fn!("shell", shell, "thickness");
+        add_number_fn!("round_edges", round_edges, "r");
+
+        prelude.add_fn("displace", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[
+                    &[Param::shape("shape"), Param::field("field")],
+                    &[Param::shape("shape"), Param::fields3("offset")],
+                ],
+            )?;
+            let result = match args.form {
+                0 => displace(args.tree(0), args.tree(1)),
+                _ => {
+                    let [dx, dy, dz] = args.fields3(1);
+                    displace_xyz(args.tree(0), dx, dy, dz)
                 }
-                unexpected => unexpected_args("|Scale|", &unexpected),
+            };
+            Ok(KTree::from(result).into())
+        });
+
+        prelude.add_fn("normalize_sdf", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::shape("shape"),
+                    Param::number("epsilon").or_number(1e-6),
+                ]],
+            )?;
+            match normalize_sdf(&args.tree(0), args.number(1)) {
+                Ok(tree) => Ok(KTree::from(tree).into()),
+                Err(err) => runtime_error!("normalize_sdf failed: {err}"),
             }
         });
 
+        prelude.add_fn("onion", move |ctx| {
+            let args = parse_args(
+                ctx.args(),
+                &[&[
+                    Param::shape("shape"),
+                    Param::number("thickness"),
+                    Param::number("layers"),
+                ]],
+            )?;
+            let result = onion(args.tree(0), args.number(1), args.number(2));
+            Ok(KTree::from(result).into())
+        });
+
+        if settings.rhai_compat {
+            let context_clone = context.clone();
+            prelude.add_fn("draw_rgb", move |ctx| {
+                let args = parse_args(
+                    ctx.args(),
+                    &[&[
+                        Param::shape("shape"),
+                        Param::number("r"),
+                        Param::number("g"),
+                        Param::number("b"),
+                    ]],
+                )?;
+                context_clone.lock().unwrap().shapes.push(DrawShape {
+                    tree: args.tree(0),
+                    color_rgb: [args.number(1), args.number(2), args.number(3)].map(to_u8),
+                    name: None,
+                    vars: vec![],
+                });
+                Ok(KValue::Null)
+            });
+            add_rhai_fns(&prelude);
+        }
+
         Self {
             settings,
             engine: koto,
             context,
+            vars,
         }
     }
 
     /// Executes a full script
+    ///
+    /// Unless disabled in the settings, the drawn shapes are optimized before
+    /// being returned; see [`ScriptContext::optimize`].
     pub fn run(&mut self, script: &str) -> Result<ScriptContext, koto::Error> {
         self.context.lock().unwrap().clear();
 
@@ -289,12 +523,11 @@ impl Engine {
         // END Experiment
         ///////////////////////////////////////////////////////////////////////
 
-        self.engine.prelude().insert("x", KTree::x());
-        self.engine.prelude().insert("y", KTree::y());
-        self.engine.prelude().insert("z", KTree::z());
+        self.insert_vars();
 
         if self.settings.add_fidget_fns {}
 
+        let _scope = VarsScope::enter(&self.vars);
         match self.engine.compile_and_run(script) {
             Ok(_) => (),
             Err(err) => {
@@ -303,16 +536,47 @@ impl Engine {
         }
 
         // Steal the ScriptContext's contents
-        let mut lock = self.context.lock().unwrap();
-        Ok(std::mem::take(&mut lock))
+        let mut out = std::mem::take(&mut *self.context.lock().unwrap());
+        if self.settings.optimize {
+            out.optimize()
+                .map_err(|err| runtime::Error::from(format!("optimization failed: {err}")))?;
+        }
+
+        let vars = self.vars.lock().unwrap();
+        for shape in &mut out.shapes {
+            shape.vars = inspect::vars(&shape.tree)
+                .into_iter()
+                .filter_map(|var| vars.iter().find(|v| v.var == var).cloned())
+                .collect();
+        }
+        Ok(out)
+    }
+
+    /// Returns the variables declared so far, by the host or with `var(name)`
+    pub fn vars(&self) -> Vec<NamedVar> {
+        self.vars.lock().unwrap().clone()
+    }
+
+    /// (Re)defines `x`, `y`, `z` and the host's variables in the root scope
+    fn insert_vars(&self) {
+        let prelude = self.engine.prelude();
+        prelude.insert("x", KTree::x());
+        prelude.insert("y", KTree::y());
+        prelude.insert("z", KTree::z());
+        for v in self.vars.lock().unwrap().iter() {
+            if self.settings.vars.iter().any(|(name, _)| *name == v.name) {
+                prelude.insert(v.name.as_str(), KTree::from(Tree::from(v.var)));
+            }
+        }
     }
 
     /// Evaluates a single expression, in terms of `x`, `y`, and `z`
+    ///
+    /// Variables declared in the [`EngineSettings`] are defined as well.
     pub fn eval(&mut self, script: &str) -> Result<Tree, koto::Error> {
-        self.engine.prelude().insert("x", KTree::x());
-        self.engine.prelude().insert("y", KTree::y());
-        self.engine.prelude().insert("z", KTree::z());
+        self.insert_vars();
 
+        let _scope = VarsScope::enter(&self.vars);
         match self.engine.compile_and_run(script) {
             Ok(KValue::Object(obj)) => match maybe_tree(&obj) {
                 Some(tree) => Ok(tree),
@@ -333,6 +597,47 @@ impl Engine {
     }
 }
 
+thread_local! {
+    /// Variables of the engine which is running a script on this thread
+    static RUNNING_VARS: RefCell<Option<Arc<Mutex<Vec<NamedVar>>>>> = const { RefCell::new(None) };
+}
+
+/// Returns the variables of the engine which is running a script on this
+/// thread, or nothing outside of [`Engine::run`] and [`Engine::eval`]
+///
+/// Methods called from scripts use this to see variables declared with
+/// `var(name)`, since they don't have access to the engine.
+pub(crate) fn running_vars() -> Vec<NamedVar> {
+    RUNNING_VARS.with(|vars| match &*vars.borrow() {
+        Some(vars) => vars.lock().unwrap().clone(),
+        None => vec![],
+    })
+}
+
+/// Publishes an engine's variables to [`running_vars`] until it is dropped
+struct VarsScope(Option<Arc<Mutex<Vec<NamedVar>>>>);
+
+impl VarsScope {
+    fn enter(vars: &Arc<Mutex<Vec<NamedVar>>>) -> Self {
+        Self(RUNNING_VARS.with(|v| v.replace(Some(vars.clone()))))
+    }
+}
+
+impl Drop for VarsScope {
+    fn drop(&mut self) {
+        RUNNING_VARS.with(|v| *v.borrow_mut() = self.0.take());
+    }
+}
+
+/// Creates a variable with a fresh [`Var`]
+fn new_var(name: &str, default: f64) -> NamedVar {
+    NamedVar {
+        name: name.to_owned(),
+        var: Var::new(),
+        default,
+    }
+}
+
 /// Koto axes doc: TODO
 fn axes(_ctx: &mut CallContext) -> runtime::Result<KValue> {
     let (x, y, z) = Tree::axes();
@@ -347,7 +652,8 @@ fn add_fidget_module_or_fns(module: &KMap) {
     macro_rules! add_unary_fn {
         ($name_string:literal, $name:ident) => {
             module.add_fn($name_string, move |ctx| {
-                let args = ctx.args();
+                let args = flatten_vectors(ctx.args());
+                let args = args.as_slice();
                 if args.len() != 1 {
                     return unexpected_args("1 argument: Tree | Number", args);
                 }
@@ -366,7 +672,8 @@ fn add_fidget_module_or_fns(module: &KMap) {
     macro_rules! add_binary_fn {
         ($name_string:literal, $name:ident) => {
             module.add_fn($name_string, move |ctx| {
-                let args = ctx.args();
+                let args = flatten_vectors(ctx.args());
+                let args = args.as_slice();
                 if args.len() != 2 {
                     return unexpected_args("2 arguments: Tree|Number, Tree|Number", args);
                 }
@@ -426,10 +733,46 @@ fn add_fidget_module_or_fns(module: &KMap) {
     add_unary_fn!("ceil", ceil);
     add_unary_fn!("floor", floor);
     add_unary_fn!("round", round);
+
+    // Shader-style helpers, with GLSL argument order
+    macro_rules! add_shader_fn {
+        ($name_string:literal, |$($arg:ident),+| $body:expr) => {
+            module.add_fn($name_string, move |ctx| {
+                let args = ctx.args();
+                let expected = [$(stringify!($arg)),+]
+                    .map(|arg| format!("{arg}: Tree|Number"))
+                    .join(", ");
+                let expected = format!("|{expected}|");
+                match args {
+                    [$($arg),+] => match ($(maybe_tree_or_number($arg)),+) {
+                        ($(Some($arg)),+) => Ok(KTree::from($body).into()),
+                        _ => unexpected_args(&expected, args),
+                    },
+                    _ => unexpected_args(&expected, args),
+                }
+            });
+        };
+    }
+
+    add_shader_fn!("clamp", |x, lo, hi| x.clamp(lo, hi));
+    add_shader_fn!("mix", |a, b, t| a.mix(b, t));
+    add_shader_fn!("lerp", |a, b, t| a.mix(b, t));
+    add_shader_fn!("smoothstep", |edge0, edge1, x| x.smoothstep(edge0, edge1));
+    add_shader_fn!("step", |edge, x| x.step(edge));
+    add_shader_fn!("sign", |x| x.sign());
+    add_shader_fn!("fract", |x| x.fract());
+    add_shader_fn!("mod", |x, m| x.floor_mod(m));
+    add_shader_fn!("hypot", |x, y| x.hypot(y));
+    add_shader_fn!("length2", |x, y| x.hypot(y));
+    add_shader_fn!("length3", |x, y, z| x.length3(y, z));
+    add_shader_fn!("radians", |x| x.radians());
+    add_shader_fn!("degrees", |x| x.degrees());
+
+    module.insert("pi", std::f64::consts::PI);
+    module.insert("tau", std::f64::consts::TAU);
 }
 
-fn to_u8(number: &KNumber) -> u8 {
-    let number = f64::from(number);
+fn to_u8(number: f64) -> u8 {
     if number < 0.0 {
         0
     } else if number > 1.0 {
@@ -0,0 +1,356 @@
+//! Introspection of [`Tree`]s, for debugging models
+//!
+//! Trees are imported into a [`Context`] first, so shared subtrees are only
+//! counted once and remapped axes are resolved.
+//!
+//! In Koto:
+//!
+//! ```koto
+//! t = (x.square() + y.square()).sqrt() - 1
+//! print t                   # Tree(sqrt(square(x) + square(y)) - 1)
+//! print t.sexpr()           # (sub (sqrt (add (square x) (square y))) 1)
+//! print t.node_count()      # 8
+//! print t.vars()            # ["x", "y"]
+//! ```
+
+use crate::NamedVar;
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Maximum length of the expression printed by `Display`
+pub const DISPLAY_LEN: usize = 80;
+
+fn import(tree: &Tree) -> (Context, Node) {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+    (ctx, root)
+}
+
+/// Children of a node, in order
+pub(crate) fn children(op: &Op) -> Vec<Node> {
+    match op {
+        Op::Input(_) | Op::Const(_) => vec![],
+        Op::Unary(_, a) => vec![*a],
+        Op::Binary(_, a, b) => vec![*a, *b],
+    }
+}
+
+/// Visits every unique node below `root` once, children before parents
+pub(crate) fn post_order(ctx: &Context, root: Node) -> Vec<Node> {
+    let mut out = vec![];
+    let mut seen = HashSet::new();
+    // Explicit stack, so that deep trees don't overflow the call stack
+    let mut stack = vec![(root, false)];
+    while let Some((node, expanded)) = stack.pop() {
+        if expanded {
+            out.push(node);
+            continue;
+        }
+        if !seen.insert(node) {
+            continue;
+        }
+        stack.push((node, true));
+        let op = ctx.get_op(node).unwrap();
+        for child in children(op).into_iter().rev() {
+            stack.push((child, false));
+        }
+    }
+    out
+}
+
+/// Returns the number of unique operations in the tree
+pub fn node_count(tree: &Tree) -> usize {
+    let (ctx, root) = import(tree);
+    post_order(&ctx, root).len()
+}
+
+/// Returns the length of the longest path from the root to a leaf
+///
+/// A tree with a single node has a depth of 1.
+pub fn depth(tree: &Tree) -> usize {
+    let (ctx, root) = import(tree);
+    let mut depths: HashMap<Node, usize> = HashMap::new();
+    for node in post_order(&ctx, root) {
+        let op = ctx.get_op(node).unwrap();
+        let d = children(op).iter().map(|c| depths[c]).max().unwrap_or(0) + 1;
+        depths.insert(node, d);
+    }
+    depths[&root]
+}
+
+/// Returns the variables used by the tree, with `x`, `y` and `z` first
+pub fn vars(tree: &Tree) -> Vec<Var> {
+    let (ctx, root) = import(tree);
+    let mut out = vec![];
+    for node in post_order(&ctx, root) {
+        match ctx.get_op(node) {
+            Some(Op::Input(var)) if !out.contains(var) => out.push(*var),
+            _ => (),
+        }
+    }
+    let order = |v: &Var| match v {
+        Var::X => 0,
+        Var::Y => 1,
+        Var::Z => 2,
+        _ => 3,
+    };
+    out.sort_by_key(order);
+    out
+}
+
+/// Checks whether the tree doesn't depend on any variable
+pub fn is_constant(tree: &Tree) -> bool {
+    vars(tree).is_empty()
+}
+
+/// Returns the name of a variable, as used in printed expressions
+///
+/// Variables other than `x`, `y` and `z` are looked up in `vars`, such as
+/// [`DrawShape::vars`](crate::DrawShape::vars) or
+/// [`Engine::vars`](crate::Engine::vars).
+pub fn var_name(var: &Var, vars: &[NamedVar]) -> String {
+    match var {
+        Var::X => "x".to_owned(),
+        Var::Y => "y".to_owned(),
+        Var::Z => "z".to_owned(),
+        v => match vars.iter().find(|n| n.var == *v) {
+            Some(n) => n.name.clone(),
+            None => format!("{v:?}"),
+        },
+    }
+}
+
+fn unary_name(opcode: UnaryOpcode) -> String {
+    format!("{opcode:?}").to_lowercase()
+}
+
+fn binary_name(opcode: BinaryOpcode) -> &'static str {
+    match opcode {
+        BinaryOpcode::Add => "add",
+        BinaryOpcode::Sub => "sub",
+        BinaryOpcode::Mul => "mul",
+        BinaryOpcode::Div => "div",
+        BinaryOpcode::Atan => "atan2",
+        BinaryOpcode::Min => "min",
+        BinaryOpcode::Max => "max",
+        BinaryOpcode::Compare => "compare",
+        BinaryOpcode::Mod => "mod",
+        BinaryOpcode::And => "and",
+        BinaryOpcode::Or => "or",
+    }
+}
+
+/// Precedence of infix operators, or `None` for function-style operations
+fn precedence(op: &Op) -> Option<u8> {
+    match op {
+        Op::Binary(BinaryOpcode::Add | BinaryOpcode::Sub, ..) => Some(1),
+        Op::Binary(BinaryOpcode::Mul | BinaryOpcode::Div, ..) => Some(2),
+        Op::Unary(UnaryOpcode::Neg, _) => Some(3),
+        _ => None,
+    }
+}
+
+/// Nesting depth at which infix expressions are cut short
+///
+/// The left operand of a long `+` or `*` chain is nested one level deeper per
+/// term without writing anything, so the byte limit alone doesn't bound the
+/// recursion.
+const MAX_INFIX_DEPTH: usize = 256;
+
+/// Writes an infix expression, stopping with `…` once `out` reaches `limit`
+/// or the expression is nested too deeply
+///
+/// Returns `false` if the expression was cut short.
+fn write_infix(
+    ctx: &Context,
+    node: Node,
+    vars: &[NamedVar],
+    out: &mut String,
+    limit: usize,
+    depth: usize,
+) -> bool {
+    if out.len() >= limit || depth >= MAX_INFIX_DEPTH {
+        out.push('…');
+        return false;
+    }
+    let op = ctx.get_op(node).unwrap();
+    // Wraps a child in parentheses if it binds less tightly than `min`
+    let child = |out: &mut String, c: Node, min: u8| {
+        let wrap = precedence(ctx.get_op(c).unwrap()).is_some_and(|p| p < min);
+        if wrap {
+            out.push('(');
+        }
+        let done = write_infix(ctx, c, vars, out, limit, depth + 1);
+        if wrap && done {
+            out.push(')');
+        }
+        done
+    };
+    match op {
+        Op::Input(var) => {
+            out.push_str(&var_name(var, vars));
+            true
+        }
+        Op::Const(c) => {
+            write!(out, "{}", c.0).unwrap();
+            true
+        }
+        Op::Unary(UnaryOpcode::Neg, a) => {
+            out.push('-');
+            child(out, *a, 3)
+        }
+        Op::Unary(opcode, a) => {
+            write!(out, "{}(", unary_name(*opcode)).unwrap();
+            let done = write_infix(ctx, *a, vars, out, limit, depth + 1);
+            if done {
+                out.push(')');
+            }
+            done
+        }
+        Op::Binary(opcode, a, b) => match precedence(op) {
+            Some(p) => {
+                let symbol = match opcode {
+                    BinaryOpcode::Add => " + ",
+                    BinaryOpcode::Sub => " - ",
+                    BinaryOpcode::Mul => " * ",
+                    _ => " / ",
+                };
+                if !child(out, *a, p) {
+                    return false;
+                }
+                out.push_str(symbol);
+                // Subtraction and division are not associative, so the
+                // right-hand side needs parentheses at the same precedence
+                let right_min = match opcode {
+                    BinaryOpcode::Sub | BinaryOpcode::Div => p + 1,
+                    _ => p,
+                };
+                child(out, *b, right_min)
+            }
+            None => {
+                write!(out, "{}(", binary_name(*opcode)).unwrap();
+                if !write_infix(ctx, *a, vars, out, limit, depth + 1) {
+                    return false;
+                }
+                out.push_str(", ");
+                let done = write_infix(ctx, *b, vars, out, limit, depth + 1);
+                if done {
+                    out.push(')');
+                }
+                done
+            }
+        },
+    }
+}
+
+/// Returns the tree as an infix expression, cut short after `max_len` bytes
+///
+/// Variables are named as in [`var_name`].
+/// Shared subtrees are written out every time they are used.  Very deeply
+/// nested expressions are also cut short; [`to_sexpr`] gives the full tree.
+pub fn to_expr(tree: &Tree, vars: &[NamedVar], max_len: usize) -> String {
+    let (ctx, root) = import(tree);
+    let mut out = String::new();
+    write_infix(&ctx, root, vars, &mut out, max_len, 0);
+    out
+}
+
+/// Returns the full tree as an s-expression
+///
+/// Variables are named as in [`var_name`].
+/// Subtrees which are used more than once are bound once in a `let` form and
+/// referred to by name (`_0`, `_1`, ...), so the output grows with the number
+/// of unique nodes rather than the size of the expanded tree.
+pub fn to_sexpr(tree: &Tree, vars: &[NamedVar]) -> String {
+    let (ctx, root) = import(tree);
+    let order = post_order(&ctx, root);
+
+    let mut uses: HashMap<Node, usize> = HashMap::new();
+    for node in &order {
+        for c in children(ctx.get_op(*node).unwrap()) {
+            *uses.entry(c).or_default() += 1;
+        }
+    }
+
+    // Shared operations are named in post-order, so every binding only
+    // refers to names bound before it
+    let mut names: HashMap<Node, usize> = HashMap::new();
+    let mut shared = vec![];
+    for node in order {
+        let used = uses.get(&node).is_some_and(|n| *n > 1);
+        if used && !children(ctx.get_op(node).unwrap()).is_empty() {
+            names.insert(node, shared.len());
+            shared.push(node);
+        }
+    }
+
+    let mut out = String::new();
+    if shared.is_empty() {
+        write_sexpr(&ctx, root, vars, &names, &mut out);
+        return out;
+    }
+    out.push_str("(let (");
+    for (i, node) in shared.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write!(out, "(_{i} ").unwrap();
+        write_sexpr(&ctx, *node, vars, &names, &mut out);
+        out.push(')');
+    }
+    out.push_str(") ");
+    write_sexpr(&ctx, root, vars, &names, &mut out);
+    out.push(')');
+    out
+}
+
+/// Writes the s-expression for `node`, referring to other named nodes by name
+///
+/// Each unnamed operation has a single parent, so it is only written once.
+fn write_sexpr(
+    ctx: &Context,
+    node: Node,
+    vars: &[NamedVar],
+    names: &HashMap<Node, usize>,
+    out: &mut String,
+) {
+    enum Item {
+        Node(Node),
+        Text(&'static str),
+    }
+    // Explicit stack, so that deep trees don't overflow the call stack
+    let mut stack = vec![Item::Node(node)];
+    while let Some(item) = stack.pop() {
+        let n = match item {
+            Item::Text(t) => {
+                out.push_str(t);
+                continue;
+            }
+            Item::Node(n) => n,
+        };
+        if let Some(i) = names.get(&n).filter(|_| n != node) {
+            write!(out, "_{i}").unwrap();
+            continue;
+        }
+        match ctx.get_op(n).unwrap() {
+            Op::Input(var) => out.push_str(&var_name(var, vars)),
+            Op::Const(c) => write!(out, "{}", c.0).unwrap(),
+            Op::Unary(opcode, a) => {
+                write!(out, "({} ", unary_name(*opcode)).unwrap();
+                stack.push(Item::Text(")"));
+                stack.push(Item::Node(*a));
+            }
+            Op::Binary(opcode, a, b) => {
+                write!(out, "({} ", binary_name(*opcode)).unwrap();
+                stack.push(Item::Text(")"));
+                stack.push(Item::Node(*b));
+                stack.push(Item::Text(" "));
+                stack.push(Item::Node(*a));
+            }
+        }
+    }
+}
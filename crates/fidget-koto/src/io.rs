@@ -0,0 +1,667 @@
+//! Saving and loading [`Tree`]s and drawn shapes
+//!
+//! Trees are stored as a table of unique nodes, children before parents, so
+//! shared subtrees are only written once, followed by a list of entries which
+//! point into the table.  An entry is either a bare tree or a drawn shape with
+//! its color and optional name.  All entries of a file share one node table.
+//!
+//! There are two formats with the same structure, both versioned:
+//!
+//! - a line-based text format, for diffing and inspection:
+//!
+//!   ```text
+//!   fidget-koto-tree 1
+//!   x
+//!   y
+//!   square 0
+//!   square 1
+//!   add 2 3
+//!   sqrt 4
+//!   const 1
+//!   sub 5 6
+//!   shape 7 255 0 0 "red disk"
+//!   ```
+//!
+//! - a compact little-endian binary format, starting with `FKTB`
+//!
+//! Variables other than `x`, `y` and `z` are stored by name along with their
+//! default value (`var "t" 0.5`).  Loading creates a new [`Var`] for every
+//! name, shared by all entries of the file.
+//!
+//! In the text format, names are quoted; `"`, `\`, whitespace and control
+//! characters are escaped as `\"`, `\\` and `\u{..}`, so every name is a
+//! single word.
+//!
+//! [`load_tree`] and [`load_shapes`] detect the format automatically.
+
+use crate::inspect::{self, post_order};
+use crate::optimize::{apply_binary, apply_unary};
+use crate::{DrawShape, NamedVar};
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Version written by [`save_tree`] and [`save_shapes`]
+pub const VERSION: u32 = 1;
+
+const TEXT_MAGIC: &str = "fidget-koto-tree";
+const BINARY_MAGIC: &[u8; 4] = b"FKTB";
+
+/// On-disk format
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Line-based text
+    #[default]
+    Text,
+    /// Compact binary
+    Binary,
+}
+
+/// Error when saving or loading trees
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Rebuilding a tree failed
+    #[error(transparent)]
+    Fidget(#[from] fidget::Error),
+    /// The data doesn't start with a known header
+    #[error("not a fidget-koto tree file")]
+    BadMagic,
+    /// The file was written by a newer version
+    #[error("unsupported version {0} (expected at most {VERSION})")]
+    BadVersion(u32),
+    /// A line of a text file couldn't be parsed
+    #[error("line {0}: {1}")]
+    BadLine(usize, String),
+    /// The contents are malformed
+    #[error("invalid data: {0}")]
+    BadData(String),
+    /// [`load_tree`] found zero or several trees
+    #[error("expected a single tree, found {0}")]
+    NotSingleTree(usize),
+}
+
+// Opcodes are stored by position in these tables, so new opcodes must only
+// ever be appended
+const UNARY: [(UnaryOpcode, &str); 17] = [
+    (UnaryOpcode::Neg, "neg"),
+    (UnaryOpcode::Abs, "abs"),
+    (UnaryOpcode::Recip, "recip"),
+    (UnaryOpcode::Sqrt, "sqrt"),
+    (UnaryOpcode::Square, "square"),
+    (UnaryOpcode::Floor, "floor"),
+    (UnaryOpcode::Ceil, "ceil"),
+    (UnaryOpcode::Round, "round"),
+    (UnaryOpcode::Sin, "sin"),
+    (UnaryOpcode::Cos, "cos"),
+    (UnaryOpcode::Tan, "tan"),
+    (UnaryOpcode::Asin, "asin"),
+    (UnaryOpcode::Acos, "acos"),
+    (UnaryOpcode::Atan, "atan"),
+    (UnaryOpcode::Exp, "exp"),
+    (UnaryOpcode::Ln, "ln"),
+    (UnaryOpcode::Not, "not"),
+];
+
+const BINARY: [(BinaryOpcode, &str); 11] = [
+    (BinaryOpcode::Add, "add"),
+    (BinaryOpcode::Sub, "sub"),
+    (BinaryOpcode::Mul, "mul"),
+    (BinaryOpcode::Div, "div"),
+    (BinaryOpcode::Atan, "atan2"),
+    (BinaryOpcode::Min, "min"),
+    (BinaryOpcode::Max, "max"),
+    (BinaryOpcode::Compare, "compare"),
+    (BinaryOpcode::Mod, "mod"),
+    (BinaryOpcode::And, "and"),
+    (BinaryOpcode::Or, "or"),
+];
+
+/// Node in the on-disk table, referring to earlier nodes by index
+#[derive(Clone, Debug, PartialEq)]
+enum Entry {
+    X,
+    Y,
+    Z,
+    Var(String, f64),
+    Const(f64),
+    Unary(u8, u32),
+    Binary(u8, u32, u32),
+}
+
+/// Tree or shape, referring to its root node by index
+#[derive(Clone, Debug, PartialEq)]
+struct Root {
+    node: u32,
+    color_rgb: Option<[u8; 3]>,
+    name: Option<String>,
+}
+
+/// Contents of a file
+#[derive(Default)]
+struct Document {
+    nodes: Vec<Entry>,
+    roots: Vec<Root>,
+}
+
+impl Document {
+    /// Builds the node table for a set of trees
+    ///
+    /// `defaults` gives the default value of variables other than `x`, `y`
+    /// and `z`; missing variables default to 0.
+    fn build<'a>(
+        trees: impl IntoIterator<Item = (&'a Tree, Option<[u8; 3]>, Option<String>)>,
+        defaults: &[NamedVar],
+    ) -> Self {
+        let mut ctx = Context::new();
+        let mut doc = Self::default();
+        let mut index: HashMap<Node, u32> = HashMap::new();
+        for (tree, color_rgb, name) in trees {
+            let root = ctx.import(tree);
+            for node in post_order(&ctx, root) {
+                if index.contains_key(&node) {
+                    continue;
+                }
+                let entry = match ctx.get_op(node).unwrap() {
+                    Op::Input(Var::X) => Entry::X,
+                    Op::Input(Var::Y) => Entry::Y,
+                    Op::Input(Var::Z) => Entry::Z,
+                    Op::Input(var) => {
+                        let default = defaults
+                            .iter()
+                            .find(|v| v.var == *var)
+                            .map_or(0.0, |v| v.default);
+                        Entry::Var(inspect::var_name(var, defaults), default)
+                    }
+                    Op::Const(c) => Entry::Const(c.0),
+                    Op::Unary(opcode, a) => {
+                        let code = UNARY.iter().position(|(op, _)| op == opcode).unwrap();
+                        Entry::Unary(code as u8, index[a])
+                    }
+                    Op::Binary(opcode, a, b) => {
+                        let code = BINARY.iter().position(|(op, _)| op == opcode).unwrap();
+                        Entry::Binary(code as u8, index[a], index[b])
+                    }
+                };
+                index.insert(node, doc.nodes.len() as u32);
+                doc.nodes.push(entry);
+            }
+            doc.roots.push(Root {
+                node: index[&root],
+                color_rgb,
+                name,
+            });
+        }
+        doc
+    }
+
+    /// Rebuilds the trees, along with the variables used in the file
+    fn trees(&self) -> Result<(Vec<Tree>, Vec<NamedVar>), Error> {
+        let mut ctx = Context::new();
+        let mut nodes: Vec<Node> = Vec::with_capacity(self.nodes.len());
+        let mut vars: Vec<NamedVar> = vec![];
+        let get = |nodes: &[Node], i: u32| {
+            nodes
+                .get(i as usize)
+                .copied()
+                .ok_or_else(|| Error::BadData(format!("node {i} is used before it is defined")))
+        };
+        for entry in &self.nodes {
+            let node = match entry {
+                Entry::X => ctx.x(),
+                Entry::Y => ctx.y(),
+                Entry::Z => ctx.z(),
+                Entry::Var(name, default) => {
+                    let var = match vars.iter().find(|v| v.name == *name) {
+                        Some(v) => v.var,
+                        None => {
+                            let var = Var::new();
+                            vars.push(NamedVar {
+                                name: name.clone(),
+                                var,
+                                default: *default,
+                            });
+                            var
+                        }
+                    };
+                    ctx.var(var)
+                }
+                Entry::Const(c) => ctx.constant(*c),
+                Entry::Unary(code, a) => {
+                    let (opcode, _) = UNARY[*code as usize];
+                    let a = get(&nodes, *a)?;
+                    apply_unary(&mut ctx, opcode, a)?
+                }
+                Entry::Binary(code, a, b) => {
+                    let (opcode, _) = BINARY[*code as usize];
+                    let (a, b) = (get(&nodes, *a)?, get(&nodes, *b)?);
+                    apply_binary(&mut ctx, opcode, a, b)?
+                }
+            };
+            nodes.push(node);
+        }
+        let trees = self
+            .roots
+            .iter()
+            .map(|root| Ok(ctx.export(get(&nodes, root.node)?)?))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok((trees, vars))
+    }
+
+    fn write(&self, format: Format, out: &mut impl Write) -> Result<(), Error> {
+        match format {
+            Format::Text => self.write_text(out),
+            Format::Binary => self.write_binary(out),
+        }
+    }
+
+    fn write_text(&self, out: &mut impl Write) -> Result<(), Error> {
+        writeln!(out, "{TEXT_MAGIC} {VERSION}")?;
+        for entry in &self.nodes {
+            match entry {
+                Entry::X => writeln!(out, "x")?,
+                Entry::Y => writeln!(out, "y")?,
+                Entry::Z => writeln!(out, "z")?,
+                Entry::Var(name, default) => writeln!(out, "var {} {default}", quote(name))?,
+                Entry::Const(c) => writeln!(out, "const {c}")?,
+                Entry::Unary(code, a) => writeln!(out, "{} {a}", UNARY[*code as usize].1)?,
+                Entry::Binary(code, a, b) => writeln!(out, "{} {a} {b}", BINARY[*code as usize].1)?,
+            }
+        }
+        for root in &self.roots {
+            match (root.color_rgb, &root.name) {
+                (Some([r, g, b]), Some(name)) => {
+                    writeln!(out, "shape {} {r} {g} {b} {}", root.node, quote(name))?
+                }
+                (Some([r, g, b]), None) => writeln!(out, "shape {} {r} {g} {b}", root.node)?,
+                (None, _) => writeln!(out, "tree {}", root.node)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_binary(&self, out: &mut impl Write) -> Result<(), Error> {
+        out.write_all(BINARY_MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.nodes.len() as u32).to_le_bytes())?;
+        for entry in &self.nodes {
+            match entry {
+                Entry::X => out.write_all(&[0])?,
+                Entry::Y => out.write_all(&[1])?,
+                Entry::Z => out.write_all(&[2])?,
+                Entry::Var(name, default) => {
+                    out.write_all(&[3])?;
+                    out.write_all(&(name.len() as u32).to_le_bytes())?;
+                    out.write_all(name.as_bytes())?;
+                    out.write_all(&default.to_le_bytes())?;
+                }
+                Entry::Const(c) => {
+                    out.write_all(&[4])?;
+                    out.write_all(&c.to_le_bytes())?;
+                }
+                Entry::Unary(code, a) => {
+                    out.write_all(&[5, *code])?;
+                    out.write_all(&a.to_le_bytes())?;
+                }
+                Entry::Binary(code, a, b) => {
+                    out.write_all(&[6, *code])?;
+                    out.write_all(&a.to_le_bytes())?;
+                    out.write_all(&b.to_le_bytes())?;
+                }
+            }
+        }
+        out.write_all(&(self.roots.len() as u32).to_le_bytes())?;
+        for root in &self.roots {
+            match (root.color_rgb, &root.name) {
+                (Some(rgb), Some(name)) => {
+                    out.write_all(&[2])?;
+                    out.write_all(&root.node.to_le_bytes())?;
+                    out.write_all(&rgb)?;
+                    out.write_all(&(name.len() as u32).to_le_bytes())?;
+                    out.write_all(name.as_bytes())?;
+                }
+                (Some(rgb), None) => {
+                    out.write_all(&[1])?;
+                    out.write_all(&root.node.to_le_bytes())?;
+                    out.write_all(&rgb)?;
+                }
+                (None, _) => {
+                    out.write_all(&[0])?;
+                    out.write_all(&root.node.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(input: &mut impl Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        input.read_to_end(&mut data)?;
+        if data.starts_with(BINARY_MAGIC) {
+            Self::read_binary(&data[BINARY_MAGIC.len()..])
+        } else if data.starts_with(TEXT_MAGIC.as_bytes()) {
+            let text = std::str::from_utf8(&data)
+                .map_err(|err| Error::BadData(format!("invalid UTF-8: {err}")))?;
+            Self::read_text(text)
+        } else {
+            Err(Error::BadMagic)
+        }
+    }
+
+    fn read_text(text: &str) -> Result<Self, Error> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let (_, header) = lines.next().ok_or(Error::BadMagic)?;
+        let version: u32 = match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [TEXT_MAGIC, version] => version.parse().map_err(|_| Error::BadMagic)?,
+            _ => return Err(Error::BadMagic),
+        };
+        if version > VERSION {
+            return Err(Error::BadVersion(version));
+        }
+
+        let mut doc = Self::default();
+        for (line_number, line) in lines {
+            let bad = |msg: &str| Error::BadLine(line_number, format!("{msg}: `{line}`"));
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let index = |s: &str| s.parse::<u32>().map_err(|_| bad("invalid node index"));
+            let number = |s: &str| s.parse::<f64>().map_err(|_| bad("invalid number"));
+            let channel = |s: &str| s.parse::<u8>().map_err(|_| bad("invalid color"));
+            let quoted = |s: &str| unquote(s).ok_or_else(|| bad("invalid name"));
+            let entry = match words.as_slice() {
+                [] => continue,
+                [word, ..] if word.starts_with('#') => continue,
+                ["x"] => Entry::X,
+                ["y"] => Entry::Y,
+                ["z"] => Entry::Z,
+                ["var", n, default] => Entry::Var(quoted(n)?, number(default)?),
+                ["const", c] => Entry::Const(number(c)?),
+                ["tree", root] => {
+                    doc.roots.push(Root {
+                        node: index(root)?,
+                        color_rgb: None,
+                        name: None,
+                    });
+                    continue;
+                }
+                ["shape", root, r, g, b, rest @ ..] => {
+                    let name = match rest {
+                        [] => None,
+                        [n] => Some(quoted(n)?),
+                        _ => return Err(bad("unknown node")),
+                    };
+                    doc.roots.push(Root {
+                        node: index(root)?,
+                        color_rgb: Some([channel(r)?, channel(g)?, channel(b)?]),
+                        name,
+                    });
+                    continue;
+                }
+                [op, a] => match UNARY.iter().position(|(_, name)| name == op) {
+                    Some(code) => Entry::Unary(code as u8, index(a)?),
+                    None => return Err(bad("unknown unary operation")),
+                },
+                [op, a, b] => match BINARY.iter().position(|(_, name)| name == op) {
+                    Some(code) => Entry::Binary(code as u8, index(a)?, index(b)?),
+                    None => return Err(bad("unknown binary operation")),
+                },
+                _ => return Err(bad("unknown node")),
+            };
+            doc.nodes.push(entry);
+        }
+        Ok(doc)
+    }
+
+    fn read_binary(mut data: &[u8]) -> Result<Self, Error> {
+        let version = read_u32(&mut data)?;
+        if version > VERSION {
+            return Err(Error::BadVersion(version));
+        }
+        let mut doc = Self::default();
+        for _ in 0..read_u32(&mut data)? {
+            let entry = match read_u8(&mut data)? {
+                0 => Entry::X,
+                1 => Entry::Y,
+                2 => Entry::Z,
+                3 => {
+                    let len = read_u32(&mut data)? as usize;
+                    let name = take(&mut data, len)?;
+                    let name = String::from_utf8(name.to_vec())
+                        .map_err(|_| Error::BadData("invalid variable name".to_owned()))?;
+                    Entry::Var(name, read_f64(&mut data)?)
+                }
+                4 => Entry::Const(read_f64(&mut data)?),
+                5 => {
+                    let code = read_u8(&mut data)?;
+                    if code as usize >= UNARY.len() {
+                        return Err(Error::BadData(format!("unknown unary opcode {code}")));
+                    }
+                    Entry::Unary(code, read_u32(&mut data)?)
+                }
+                6 => {
+                    let code = read_u8(&mut data)?;
+                    if code as usize >= BINARY.len() {
+                        return Err(Error::BadData(format!("unknown binary opcode {code}")));
+                    }
+                    Entry::Binary(code, read_u32(&mut data)?, read_u32(&mut data)?)
+                }
+                tag => return Err(Error::BadData(format!("unknown node tag {tag}"))),
+            };
+            doc.nodes.push(entry);
+        }
+        for _ in 0..read_u32(&mut data)? {
+            let kind = read_u8(&mut data)?;
+            let node = read_u32(&mut data)?;
+            let color_rgb = match kind {
+                0 => None,
+                1 | 2 => Some([
+                    read_u8(&mut data)?,
+                    read_u8(&mut data)?,
+                    read_u8(&mut data)?,
+                ]),
+                kind => return Err(Error::BadData(format!("unknown entry kind {kind}"))),
+            };
+            let name = if kind == 2 {
+                let len = read_u32(&mut data)? as usize;
+                let name = String::from_utf8(take(&mut data, len)?.to_vec())
+                    .map_err(|_| Error::BadData("invalid shape name".to_owned()))?;
+                Some(name)
+            } else {
+                None
+            };
+            doc.roots.push(Root {
+                node,
+                color_rgb,
+                name,
+            });
+        }
+        if !data.is_empty() {
+            return Err(Error::BadData("trailing bytes".to_owned()));
+        }
+        Ok(doc)
+    }
+}
+
+/// Quotes a name for the text format, see the [module docs](self)
+fn quote(name: &str) -> String {
+    let mut out = String::from('"');
+    for c in name.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_whitespace() || c.is_control() => {
+                out.push_str(&format!("\\u{{{:x}}}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Parses a name written by [`quote`]
+fn unquote(word: &str) -> Option<String> {
+    let mut chars = word.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return None,
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\') => out.push(c),
+                'u' => {
+                    let rest = chars.as_str().strip_prefix('{')?;
+                    let (hex, rest) = rest.split_once('}')?;
+                    out.push(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?);
+                    chars = rest.chars();
+                }
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8], Error> {
+    if data.len() < n {
+        return Err(Error::BadData("unexpected end of data".to_owned()));
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, Error> {
+    Ok(take(data, 1)?[0])
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into().unwrap()))
+}
+
+fn read_f64(data: &mut &[u8]) -> Result<f64, Error> {
+    Ok(f64::from_le_bytes(take(data, 8)?.try_into().unwrap()))
+}
+
+/// Writes a single tree
+///
+/// Variables other than `x`, `y` and `z` are saved without names or defaults;
+/// use [`save_shapes`] to keep them.
+pub fn save_tree(tree: &Tree, format: Format, out: &mut impl Write) -> Result<(), Error> {
+    Document::build([(tree, None, None)], &[]).write(format, out)
+}
+
+/// Reads a file written by [`save_tree`]
+///
+/// Files with more than one tree or shape are rejected; use [`load_shapes`]
+/// for those.
+pub fn load_tree(input: &mut impl Read) -> Result<Tree, Error> {
+    let doc = Document::read(input)?;
+    if doc.roots.len() != 1 {
+        return Err(Error::NotSingleTree(doc.roots.len()));
+    }
+    let (mut trees, _vars) = doc.trees()?;
+    Ok(trees.pop().unwrap())
+}
+
+/// Writes a set of shapes, with their colors, names and variable defaults
+///
+/// Subtrees shared between shapes are only stored once.
+pub fn save_shapes(
+    shapes: &[DrawShape],
+    format: Format,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    let defaults = shapes
+        .iter()
+        .flat_map(|s| s.vars.iter().cloned())
+        .collect::<Vec<_>>();
+    let trees = shapes
+        .iter()
+        .map(|s| (&s.tree, Some(s.color_rgb), s.name.clone()));
+    Document::build(trees, &defaults).write(format, out)
+}
+
+/// Reads a file written by [`save_shapes`] or [`save_tree`]
+///
+/// Bare trees are loaded as white shapes.
+pub fn load_shapes(input: &mut impl Read) -> Result<Vec<DrawShape>, Error> {
+    let doc = Document::read(input)?;
+    let (trees, vars) = doc.trees()?;
+    let shapes = trees
+        .into_iter()
+        .zip(&doc.roots)
+        .map(|(tree, root)| DrawShape {
+            vars: inspect::vars(&tree)
+                .into_iter()
+                .filter_map(|var| vars.iter().find(|v| v.var == var).cloned())
+                .collect(),
+            tree,
+            color_rgb: root.color_rgb.unwrap_or([255; 3]),
+            name: root.name.clone(),
+        })
+        .collect();
+    Ok(shapes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shapes() -> Vec<DrawShape> {
+        let t = NamedVar {
+            name: "my \"var\"\n".to_owned(),
+            var: Var::new(),
+            default: 0.5,
+        };
+        let r = (Tree::x().square() + Tree::y().square()).sqrt();
+        vec![
+            DrawShape {
+                tree: r.clone() - Tree::from(t.var),
+                color_rgb: [255, 0, 0],
+                name: Some("red disk".to_owned()),
+                vars: vec![t],
+            },
+            DrawShape {
+                tree: r * Tree::constant(2.0),
+                color_rgb: [0, 0, 255],
+                name: Some(String::new()),
+                vars: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for format in [Format::Text, Format::Binary] {
+            let mut saved = vec![];
+            save_shapes(&shapes(), format, &mut saved).unwrap();
+            let loaded = load_shapes(&mut saved.as_slice()).unwrap();
+
+            assert_eq!(loaded.len(), 2);
+            assert_eq!(loaded[0].name.as_deref(), Some("red disk"));
+            assert_eq!(loaded[0].color_rgb, [255, 0, 0]);
+            assert_eq!(loaded[1].name.as_deref(), Some(""));
+            assert_eq!(loaded[1].color_rgb, [0, 0, 255]);
+            let [v] = loaded[0].vars.as_slice() else {
+                panic!("expected one variable");
+            };
+            assert_eq!(v.name, "my \"var\"\n");
+            assert_eq!(v.default, 0.5);
+            assert!(loaded[1].vars.is_empty());
+
+            // Saving again gives the same file, with shared nodes stored once
+            let mut again = vec![];
+            save_shapes(&loaded, format, &mut again).unwrap();
+            assert_eq!(saved, again);
+            let doc = Document::read(&mut saved.as_slice()).unwrap();
+            assert_eq!(doc.nodes.len(), 10);
+        }
+    }
+}
@@ -1,5 +1,11 @@
+use crate::diff;
+use crate::engine::running_vars;
+use crate::inspect;
+use crate::math::ShaderMath;
+use crate::utils::maybe_tree;
+use crate::vec::{KVec3, Scalar};
 use fidget::context::Tree;
-use koto::{derive::*, prelude::*, runtime};
+use koto::{derive::*, prelude::*, runtime, runtime::runtime_error};
 use std::fmt;
 use std::ops::{Add, Div, Mul, Sub};
 
@@ -142,7 +148,11 @@ impl From<KTree> for KValue {
 
 impl fmt::Display for KTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Tree{{}}")
+        write!(
+            f,
+            "Tree({})",
+            inspect::to_expr(&self.0, &running_vars(), inspect::DISPLAY_LEN)
+        )
     }
 }
 
@@ -195,6 +205,48 @@ impl KTree {
         }
     }
 
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
+
     #[koto_method]
     fn min(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
         binary_fn!(ctx, min)
@@ -225,6 +277,125 @@ impl KTree {
         binary_fn!(ctx, atan2)
     }
 
+    #[koto_method]
+    fn clamp(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        ternary_fn!(ctx, clamp)
+    }
+
+    #[koto_method(alias = "lerp")]
+    fn mix(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        ternary_fn!(ctx, mix)
+    }
+
+    #[koto_method]
+    fn smoothstep(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        ternary_fn!(ctx, smoothstep)
+    }
+
+    #[koto_method]
+    fn step(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        binary_fn!(ctx, step)
+    }
+
+    #[koto_method(alias = "mod")]
+    fn floor_mod(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        binary_fn!(ctx, floor_mod)
+    }
+
+    #[koto_method(alias = "length2")]
+    fn hypot(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        binary_fn!(ctx, hypot)
+    }
+
+    #[koto_method]
+    fn length3(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        ternary_fn!(ctx, length3)
+    }
+
+    #[koto_method]
+    fn sign(&self) -> runtime::Result<KValue> {
+        Ok(KValue::Object(Self(self.0.sign()).into()))
+    }
+
+    #[koto_method]
+    fn fract(&self) -> runtime::Result<KValue> {
+        Ok(KValue::Object(Self(self.0.fract()).into()))
+    }
+
+    #[koto_method]
+    fn radians(&self) -> runtime::Result<KValue> {
+        Ok(KValue::Object(Self(self.0.radians()).into()))
+    }
+
+    #[koto_method]
+    fn degrees(&self) -> runtime::Result<KValue> {
+        Ok(KValue::Object(Self(self.0.degrees()).into()))
+    }
+
+    /// Partial derivative with respect to a variable, e.g. `x`
+    #[koto_method]
+    fn deriv(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let var = match ctx.args {
+            [KValue::Object(obj)] => maybe_tree(obj).and_then(|tree| diff::as_var(&tree)),
+            _ => None,
+        };
+        let Some(var) = var else {
+            return unexpected_args("|Tree|, with the Tree being a variable such as x", ctx.args);
+        };
+        match diff::deriv(&ctx.instance()?.inner(), var) {
+            Ok(tree) => Ok(KValue::Object(Self(tree).into())),
+            Err(err) => runtime_error!("deriv failed: {err}"),
+        }
+    }
+
+    /// Number of unique operations in the tree
+    #[koto_method]
+    fn node_count(&self) -> runtime::Result<KValue> {
+        Ok(inspect::node_count(&self.0).into())
+    }
+
+    /// Length of the longest path from the root to a leaf
+    #[koto_method]
+    fn depth(&self) -> runtime::Result<KValue> {
+        Ok(inspect::depth(&self.0).into())
+    }
+
+    /// Names of the variables used by the tree
+    #[koto_method]
+    fn vars(&self) -> runtime::Result<KValue> {
+        let names = running_vars();
+        let vars = inspect::vars(&self.0)
+            .iter()
+            .map(|var| inspect::var_name(var, &names).into())
+            .collect::<Vec<KValue>>();
+        Ok(KValue::List(KList::from_slice(&vars)))
+    }
+
+    /// Checks whether the tree doesn't depend on any variable
+    #[koto_method]
+    fn is_constant(&self) -> runtime::Result<KValue> {
+        Ok(inspect::is_constant(&self.0).into())
+    }
+
+    /// Full tree as an s-expression
+    #[koto_method]
+    fn sexpr(&self) -> runtime::Result<KValue> {
+        Ok(inspect::to_sexpr(&self.0, &running_vars()).into())
+    }
+
+    /// Gradient, as a `Vec3` of trees
+    #[koto_method]
+    fn grad(&self) -> runtime::Result<KValue> {
+        match diff::grad(&self.0) {
+            Ok([dx, dy, dz]) => Ok(KValue::Object(KVec3::new(
+                Scalar::Tree(dx),
+                Scalar::Tree(dy),
+                Scalar::Tree(dz),
+            ))),
+            Err(err) => runtime_error!("grad failed: {err}"),
+        }
+    }
+
     #[koto_method]
     fn abs(&self) -> runtime::Result<KValue> {
         Ok(KValue::Object(Self(self.0.clone().abs()).into()))
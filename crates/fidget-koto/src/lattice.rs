@@ -0,0 +1,146 @@
+//! Triply periodic minimal surface (TPMS) lattices
+//!
+//! Each surface is built from trigonometric [`Tree`] operations and scaled so
+//! that its value approximates a distance near the surface.  The lattices are
+//! infinite; intersect them with a shape to use them as infill.
+//!
+//! In Koto, the lattices are available through the `lattice` module:
+//!
+//! ```koto
+//! draw (sphere 1).tree().max(lattice.gyroid 0.25, 0.03)
+//! ```
+
+use crate::KTree;
+use crate::args::{Param, parse_args};
+use fidget::context::Tree;
+use koto::{prelude::*, runtime::runtime_error};
+use std::f64::consts::TAU;
+
+/// Triply periodic minimal surface type
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Surface {
+    /// Schoen gyroid
+    Gyroid,
+    /// Schwarz P (primitive) surface
+    SchwarzP,
+    /// Schwarz D (diamond) surface
+    SchwarzD,
+    /// Neovius surface
+    Neovius,
+    /// Lidinoid surface
+    Lidinoid,
+}
+
+/// How the surface is turned into a solid
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// A wall of the given thickness, centered on the surface
+    Sheet,
+    /// The region on one side of the surface, grown by half the thickness
+    Solid,
+}
+
+impl Mode {
+    /// Parses a mode name (`"sheet"` or `"solid"`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sheet" => Some(Self::Sheet),
+            "solid" => Some(Self::Solid),
+            _ => None,
+        }
+    }
+}
+
+impl Surface {
+    /// Evaluates the implicit surface with a period of `2π` on all axes
+    fn implicit(&self, x: Tree, y: Tree, z: Tree) -> Tree {
+        let (sx, sy, sz) = (x.clone().sin(), y.clone().sin(), z.clone().sin());
+        let (cx, cy, cz) = (x.clone().cos(), y.clone().cos(), z.clone().cos());
+        match self {
+            Self::Gyroid => sx * cy.clone() + sy * cz.clone() + sz * cx.clone(),
+            Self::SchwarzP => cx + cy + cz,
+            Self::SchwarzD => {
+                sx.clone() * sy.clone() * sz.clone()
+                    + sx * cy.clone() * cz.clone()
+                    + cx.clone() * sy * cz.clone()
+                    + cx * cy * sz
+            }
+            Self::Neovius => (cx.clone() + cy.clone() + cz.clone()) * 3.0 + cx * cy * cz * 4.0,
+            Self::Lidinoid => {
+                let (s2x, s2y, s2z) = (
+                    (x.clone() * 2.0).sin(),
+                    (y.clone() * 2.0).sin(),
+                    (z.clone() * 2.0).sin(),
+                );
+                let (c2x, c2y, c2z) = ((x * 2.0).cos(), (y * 2.0).cos(), (z * 2.0).cos());
+                (s2x * cy * sz.clone() + s2y * cz * sx.clone() + s2z * cx * sy) * 0.5
+                    - (c2x.clone() * c2y.clone() + c2y * c2z.clone() + c2z * c2x) * 0.5
+                    + 0.15
+            }
+        }
+    }
+
+    /// Typical gradient magnitude of [`implicit`](Self::implicit) at the
+    /// surface, used to turn it into an approximate distance
+    fn gradient_scale(&self) -> f64 {
+        match self {
+            Self::Gyroid => 1.0,
+            Self::SchwarzP => 1.0,
+            Self::SchwarzD => 1.0,
+            Self::Neovius => 3.0,
+            Self::Lidinoid => 1.0,
+        }
+    }
+}
+
+/// Builds a lattice with cells of `cell_size` and walls of `thickness`
+///
+/// `thickness` may be a spatially varying [`Tree`], which grades the lattice.
+/// `cell_size` must be positive.
+pub fn lattice(surface: Surface, cell_size: f64, thickness: Tree, mode: Mode) -> Tree {
+    let k = TAU / cell_size;
+    let (x, y, z) = Tree::axes();
+    let distance = surface.implicit(x * k, y * k, z * k) / (k * surface.gradient_scale());
+    match mode {
+        Mode::Sheet => distance.abs() - thickness / 2.0,
+        Mode::Solid => distance - thickness / 2.0,
+    }
+}
+
+/// Adds a Koto function for every [`Surface`] to the given module
+pub(crate) fn add_lattice_fns(module: &KMap) {
+    macro_rules! add_lattice_fn {
+        ($name_string:literal, $surface:expr) => {
+            module.add_fn($name_string, move |ctx| {
+                let args = parse_args(
+                    ctx.args(),
+                    &[&[
+                        Param::number("cell_size"),
+                        Param::field("thickness"),
+                        Param::string("mode").or_str("sheet"),
+                        Param::shape("grading").optional(),
+                    ]],
+                )?;
+                let Some(mode) = Mode::from_name(args.string(2)) else {
+                    return unexpected_args("mode \"sheet\" or \"solid\"", ctx.args());
+                };
+                let cell_size = args.number(0);
+                if !(cell_size > 0.0 && cell_size.is_finite()) {
+                    return runtime_error!("cell_size must be positive, got {cell_size}");
+                }
+                let mut thickness = args.tree(1);
+                if let Some(grading) = args.opt_tree(3) {
+                    thickness = thickness * grading;
+                }
+                let result = lattice($surface, cell_size, thickness, mode);
+                Ok(KTree::from(result).into())
+            });
+        };
+    }
+
+    add_lattice_fn!("gyroid", Surface::Gyroid);
+    add_lattice_fn!("schwarz_p", Surface::SchwarzP);
+    add_lattice_fn!("schwarz_d", Surface::SchwarzD);
+    add_lattice_fn!("neovius", Surface::Neovius);
+    add_lattice_fn!("lidinoid", Surface::Lidinoid);
+}
@@ -41,28 +41,52 @@
 //! # Ok::<(), fidget::Error>(())
 //! ```
 //!
-//! Within a call to [`Engine::run`], `draw` and `draw_rgb` insert shapes into  // TODO: fix doc
-//! [`ScriptContext::shapes`], which is returned after script evaluation is     // TODO: fix doc
-//! complete.                                                                   // TODO: fix doc
+//! Within a call to [`Engine::run`], `draw` inserts shapes into
+//! [`ScriptContext::shapes`], which is returned after script evaluation is
+//! complete.  With [`EngineSettings::with_rhai_compat`], `draw_rgb` does the
+//! same, for models ported from Rhai (see [`rhai`]).
 //!
 //! Scripts are evaluated in a Koto context that includes [`core.koto`](core),
 //! which defines a few simple shapes and transforms.  `x`, `y`, and `z` are
 //! defined in the root scope, and `axes()` returns an object with `x`/`y`/`z`
 //! members.
 
-use fidget::context::Tree;
+use fidget::{context::Tree, var::Var};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
 #[macro_use]
 mod macros;
 
+mod args;
+pub mod contour;
+pub mod diff;
 mod engine;
+pub mod inspect;
+pub mod io;
 mod ktree;
+pub mod lattice;
+pub mod math;
+#[cfg(feature = "mesh")]
+pub mod mesh;
+pub mod noise;
+pub mod optimize;
+pub mod query;
+pub mod render;
+pub mod rhai;
+pub mod shader;
 mod shapes;
+pub mod sweep;
 mod utils;
+pub mod vec;
+pub mod view;
 
-pub use engine::Engine;
+pub use engine::{Engine, EngineSettings};
 pub use ktree::KTree;
+pub use optimize::OptimizeStats;
 pub use shapes::{KCircle, KDifference, KIntersection, KInverse, KMove, KScale, KSphere, KUnion};
+pub use vec::{KVec2, KVec3};
 
 //////////////////////////////////////////////////////////////////////////////////
 
@@ -74,6 +98,44 @@ pub struct DrawShape {
     pub tree: Tree,
     /// Color to use when drawing the shape
     pub color_rgb: [u8; 3],
+    /// Name of the shape, used for the parts of exported files
+    pub name: Option<String>,
+    /// Variables other than `x`, `y` and `z` used by the tree
+    ///
+    /// Hosts must bind a value to each of them when evaluating the tree, e.g.
+    /// the current time for an animation.
+    pub vars: Vec<NamedVar>,
+}
+
+impl DrawShape {
+    /// Returns the tree with every variable in [`vars`](Self::vars) replaced
+    /// by its default value
+    pub fn bind_defaults(&self) -> Result<Tree, fidget::Error> {
+        if self.vars.is_empty() {
+            return Ok(self.tree.clone());
+        }
+        let values = self
+            .vars
+            .iter()
+            .map(|v| (v.var, v.default))
+            .collect::<Vec<_>>();
+        optimize::bind_vars(&self.tree, &values)
+    }
+}
+
+/// Variable other than `x`, `y` and `z`
+///
+/// Declared by the host in [`EngineSettings`] or by a script with `var(name)`.
+/// The same name always maps to the same [`Var`] within an [`Engine`], so
+/// trees from successive runs can be evaluated with the same bindings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedVar {
+    /// Name of the variable in scripts
+    pub name: String,
+    /// Variable used in trees
+    pub var: Var,
+    /// Value to use if the host doesn't bind one
+    pub default: f64,
 }
 
 /// Context for shape evaluation
@@ -83,6 +145,10 @@ pub struct DrawShape {
 pub struct ScriptContext {
     /// List of shapes populated since the last call to [`clear`](Self::clear)
     pub shapes: Vec<DrawShape>,
+    /// Node counts from the last call to [`optimize`](Self::optimize)
+    pub optimize_stats: Option<OptimizeStats>,
+    /// Camera hints populated by calls to `view`
+    pub view: view::ViewHints,
 }
 
 impl Default for ScriptContext {
@@ -94,11 +160,68 @@ impl Default for ScriptContext {
 impl ScriptContext {
     /// Builds a new empty script context
     pub fn new() -> Self {
-        Self { shapes: vec![] }
+        Self {
+            shapes: vec![],
+            optimize_stats: None,
+            view: view::ViewHints::default(),
+        }
     }
     /// Resets the script context
     pub fn clear(&mut self) {
         self.shapes.clear();
+        self.optimize_stats = None;
+        self.view = view::ViewHints::default();
+    }
+    /// Saves all shapes to a file, see [`io::save_shapes`]
+    pub fn to_file(&self, path: impl AsRef<Path>, format: io::Format) -> Result<(), io::Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        io::save_shapes(&self.shapes, format, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+    /// Loads shapes saved with [`to_file`](Self::to_file)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let shapes = io::load_shapes(&mut File::open(path)?)?;
+        Ok(Self {
+            shapes,
+            ..Self::new()
+        })
+    }
+    /// Contours every shape, see [`contour::contour_shape`]
+    pub fn contours(
+        &self,
+        settings: &contour::Settings,
+    ) -> Result<Vec<contour::ShapeContours>, fidget::Error> {
+        self.shapes
+            .iter()
+            .map(|shape| contour::contour_shape(shape, settings))
+            .collect()
+    }
+    /// Meshes every shape, see [`mesh::mesh_shape`]
+    #[cfg(feature = "mesh")]
+    pub fn mesh(
+        &self,
+        depth: u8,
+        bounds: mesh::Bounds,
+    ) -> Result<Vec<mesh::ShapeMesh>, fidget::Error> {
+        self.shapes
+            .iter()
+            .map(|shape| mesh::mesh_shape(shape, depth, bounds))
+            .collect()
+    }
+    /// Optimizes the trees of all shapes, see [`optimize::optimize`]
+    pub fn optimize(&mut self) -> Result<OptimizeStats, fidget::Error> {
+        let trees = self
+            .shapes
+            .iter()
+            .map(|s| s.tree.clone())
+            .collect::<Vec<_>>();
+        let (trees, stats) = optimize::optimize(&trees)?;
+        for (shape, tree) in self.shapes.iter_mut().zip(trees) {
+            shape.tree = tree;
+        }
+        self.optimize_stats = Some(stats);
+        Ok(stats)
     }
 }
 
@@ -133,3 +133,42 @@ macro_rules! binary_fn {
         }
     }};
 }
+
+/// Offset method for KTree and Koto shapes, see [`crate::shapes::offset_method`]
+#[macro_export]
+macro_rules! offset_fn {
+    ($ctx:ident, $op:ident) => {{
+        let tree = Tree::from($ctx.instance()?.inner());
+        crate::shapes::offset_method(crate::shapes::OffsetOp::$op, tree, $ctx.args)
+    }};
+}
+
+/// Evaluation method for KTree and Koto shapes, using a `crate::query` function
+#[macro_export]
+macro_rules! query_fn {
+    ($ctx:ident, $name:ident) => {{
+        let tree = Tree::from($ctx.instance()?.inner());
+        crate::query::$name(tree, $ctx.args)
+    }};
+}
+
+/// Ternary function for KTree, i.e. a method with two Tree or Number arguments
+#[macro_export]
+macro_rules! ternary_fn {
+    ($ctx:ident, $name:ident) => {{
+        let args = $ctx.args;
+        match args {
+            [a, b] => match (
+                crate::utils::maybe_tree_or_number(a),
+                crate::utils::maybe_tree_or_number(b),
+            ) {
+                (Some(a), Some(b)) => {
+                    let result = $ctx.instance()?.inner().$name(a, b);
+                    Ok(KValue::Object(Self(result).into()))
+                }
+                _ => unexpected_args("2 arguments: |Tree|Number, Tree|Number|", args),
+            },
+            unexpected => unexpected_args("2 arguments: |Tree|Number, Tree|Number|", unexpected),
+        }
+    }};
+}
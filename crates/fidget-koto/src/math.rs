@@ -0,0 +1,82 @@
+//! Shader-style math helpers for [`Tree`]
+//!
+//! These mirror the GLSL built-ins of the same name, which makes it easier to
+//! port SDF code written for shaders.
+
+use fidget::context::Tree;
+
+/// Shader-style math operations on [`Tree`]
+pub trait ShaderMath {
+    /// Limits the value to the range `[lo, hi]`
+    fn clamp(&self, lo: Tree, hi: Tree) -> Tree;
+    /// Linear interpolation from `self` (at `t = 0`) to `other` (at `t = 1`)
+    fn mix(&self, other: Tree, t: Tree) -> Tree;
+    /// Hermite interpolation from 0 to 1 as the value goes from `edge0` to
+    /// `edge1`, clamped outside of that range
+    fn smoothstep(&self, edge0: Tree, edge1: Tree) -> Tree;
+    /// Returns 0 if the value is below `edge`, else 1
+    fn step(&self, edge: Tree) -> Tree;
+    /// Returns -1, 0 or 1, depending on the sign of the value
+    fn sign(&self) -> Tree;
+    /// Returns the fractional part of the value, i.e. `x - floor(x)`
+    fn fract(&self) -> Tree;
+    /// Floored modulo, with the result having the sign of `m` (like GLSL `mod`)
+    fn floor_mod(&self, m: Tree) -> Tree;
+    /// Returns `sqrt(self² + other²)`
+    fn hypot(&self, other: Tree) -> Tree;
+    /// Returns `sqrt(self² + y² + z²)`
+    fn length3(&self, y: Tree, z: Tree) -> Tree;
+    /// Converts from degrees to radians
+    fn radians(&self) -> Tree;
+    /// Converts from radians to degrees
+    fn degrees(&self) -> Tree;
+}
+
+impl ShaderMath for Tree {
+    fn clamp(&self, lo: Tree, hi: Tree) -> Tree {
+        self.clone().max(lo).min(hi)
+    }
+
+    fn mix(&self, other: Tree, t: Tree) -> Tree {
+        self.clone() + (other - self.clone()) * t
+    }
+
+    fn smoothstep(&self, edge0: Tree, edge1: Tree) -> Tree {
+        let t = ((self.clone() - edge0.clone()) / (edge1 - edge0))
+            .clamp(Tree::constant(0.0), Tree::constant(1.0));
+        t.clone() * t.clone() * (t * -2.0 + 3.0)
+    }
+
+    fn step(&self, edge: Tree) -> Tree {
+        // compare returns -1, 0 or 1, so this maps `self >= edge` to 1
+        (self.clone().compare(edge) + 1.0).min(1.0)
+    }
+
+    fn sign(&self) -> Tree {
+        self.clone().compare(Tree::constant(0.0))
+    }
+
+    fn fract(&self) -> Tree {
+        self.clone() - self.clone().floor()
+    }
+
+    fn floor_mod(&self, m: Tree) -> Tree {
+        self.clone() - m.clone() * (self.clone() / m).floor()
+    }
+
+    fn hypot(&self, other: Tree) -> Tree {
+        (self.clone().square() + other.square()).sqrt()
+    }
+
+    fn length3(&self, y: Tree, z: Tree) -> Tree {
+        (self.clone().square() + y.square() + z.square()).sqrt()
+    }
+
+    fn radians(&self) -> Tree {
+        self.clone() * std::f64::consts::PI / 180.0
+    }
+
+    fn degrees(&self) -> Tree {
+        self.clone() * 180.0 / std::f64::consts::PI
+    }
+}
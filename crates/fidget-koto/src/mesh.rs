@@ -0,0 +1,576 @@
+//! Triangle meshes of drawn shapes, and writers for common mesh formats
+//!
+//! Shapes are meshed with fidget's octree mesher (see [`fidget::mesh`]).
+//! Every writer takes a slice of [`ShapeMesh`]es: pass a single mesh for
+//! per-shape files, or all of them for one merged file.
+//!
+//! STL has no colors.  OBJ and PLY store colors per vertex, while 3MF and
+//! glTF keep every shape as a separate, named part with its own color.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use fidget_koto::{Engine, mesh};
+//!
+//! let out = Engine::default().run("draw sphere 1")?;
+//! let meshes = out.mesh(7, mesh::Bounds::default())?;
+//! let mut file = std::fs::File::create("sphere.stl")?;
+//! mesh::write_stl(&meshes, &mut file)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only available with the `mesh` feature.
+
+use crate::DrawShape;
+use crate::utils::{escape_json, escape_xml};
+use fidget::{
+    mesh::{Mesh, Octree, Settings},
+    render::View3,
+    vm::VmShape,
+};
+use nalgebra::Vector3;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// Cube which is meshed, as a center and half of its side length
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    /// Center of the cube
+    pub center: [f32; 3],
+    /// Half of the side length of the cube
+    pub size: f32,
+}
+
+impl Default for Bounds {
+    /// The cube from -1 to 1 on every axis
+    fn default() -> Self {
+        Self {
+            center: [0.0; 3],
+            size: 1.0,
+        }
+    }
+}
+
+/// Mesh of a single drawn shape
+pub struct ShapeMesh {
+    /// Triangles, with vertices in model coordinates
+    pub mesh: Mesh,
+    /// Color of the shape
+    pub color_rgb: [u8; 3],
+    /// Name of the shape, if it was drawn with one
+    pub name: Option<String>,
+}
+
+impl ShapeMesh {
+    /// Returns the name of the shape, or `shape{i}` for unnamed shapes
+    fn part_name(&self, i: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("shape{i}"))
+    }
+}
+
+/// Meshes a drawn shape at the given octree depth
+///
+/// Variables other than `x`, `y` and `z` are replaced by their defaults.
+/// Each level of depth doubles the resolution along every axis.
+pub fn mesh_shape(
+    shape: &DrawShape,
+    depth: u8,
+    bounds: Bounds,
+) -> Result<ShapeMesh, fidget::Error> {
+    let tree = shape.bind_defaults()?;
+    let [cx, cy, cz] = bounds.center;
+    let settings = Settings {
+        depth,
+        view: View3::from_center_and_scale(Vector3::new(cx, cy, cz), bounds.size),
+        ..Default::default()
+    };
+    // `None` means the build was cancelled, which we never do
+    let octree = Octree::build(&VmShape::from(tree), &settings).unwrap();
+    Ok(ShapeMesh {
+        mesh: octree.walk_dual(),
+        color_rgb: shape.color_rgb,
+        name: shape.name.clone(),
+    })
+}
+
+fn normal(mesh: &Mesh, t: &Vector3<usize>) -> Vector3<f32> {
+    let [a, b, c] = [t.x, t.y, t.z].map(|i| mesh.vertices[i]);
+    let n = (b - a).cross(&(c - a));
+    let norm = n.norm();
+    if norm > 0.0 { n / norm } else { n }
+}
+
+fn triangle_count(meshes: &[ShapeMesh]) -> usize {
+    meshes.iter().map(|m| m.mesh.triangles.len()).sum()
+}
+
+/// Writes a binary STL file, which has no colors
+pub fn write_stl(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let text = b"fidget-koto";
+    header[..text.len()].copy_from_slice(text);
+    out.write_all(&header)?;
+    out.write_all(&(triangle_count(meshes) as u32).to_le_bytes())?;
+    for m in meshes {
+        for t in &m.mesh.triangles {
+            let n = normal(&m.mesh, t);
+            for v in [
+                n,
+                m.mesh.vertices[t.x],
+                m.mesh.vertices[t.y],
+                m.mesh.vertices[t.z],
+            ] {
+                for c in [v.x, v.y, v.z] {
+                    out.write_all(&c.to_le_bytes())?;
+                }
+            }
+            out.write_all(&[0, 0])?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes an ASCII STL file, which has no colors
+pub fn write_stl_ascii(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "solid fidget_koto")?;
+    for m in meshes {
+        for t in &m.mesh.triangles {
+            let n = normal(&m.mesh, t);
+            writeln!(out, "  facet normal {} {} {}", n.x, n.y, n.z)?;
+            writeln!(out, "    outer loop")?;
+            for i in [t.x, t.y, t.z] {
+                let v = m.mesh.vertices[i];
+                writeln!(out, "      vertex {} {} {}", v.x, v.y, v.z)?;
+            }
+            writeln!(out, "    endloop")?;
+            writeln!(out, "  endfacet")?;
+        }
+    }
+    writeln!(out, "endsolid fidget_koto")
+}
+
+/// Writes a Wavefront OBJ file, with one named object per shape
+///
+/// Colors are written as vertex colors (`v x y z r g b`), which most tools
+/// read and the rest ignore.
+pub fn write_obj(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let mut offset = 1;
+    for (i, m) in meshes.iter().enumerate() {
+        writeln!(out, "o {}", m.part_name(i))?;
+        let [r, g, b] = m.color_rgb.map(|c| c as f32 / 255.0);
+        for v in &m.mesh.vertices {
+            writeln!(out, "v {} {} {} {r} {g} {b}", v.x, v.y, v.z)?;
+        }
+        for t in &m.mesh.triangles {
+            writeln!(out, "f {} {} {}", t.x + offset, t.y + offset, t.z + offset)?;
+        }
+        offset += m.mesh.vertices.len();
+    }
+    Ok(())
+}
+
+/// Writes a binary little-endian PLY file, with vertex colors
+pub fn write_ply(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let vertex_count: usize = meshes.iter().map(|m| m.mesh.vertices.len()).sum();
+    write!(
+        out,
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         comment fidget-koto\n\
+         element vertex {vertex_count}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         element face {}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n",
+        triangle_count(meshes)
+    )?;
+    for m in meshes {
+        for v in &m.mesh.vertices {
+            for c in [v.x, v.y, v.z] {
+                out.write_all(&c.to_le_bytes())?;
+            }
+            out.write_all(&m.color_rgb)?;
+        }
+    }
+    let mut offset = 0;
+    for m in meshes {
+        for t in &m.mesh.triangles {
+            out.write_all(&[3])?;
+            for i in [t.x, t.y, t.z] {
+                out.write_all(&((i + offset) as u32).to_le_bytes())?;
+            }
+        }
+        offset += m.mesh.vertices.len();
+    }
+    Ok(())
+}
+
+/// Writes an ASCII PLY file, with vertex colors
+pub fn write_ply_ascii(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let vertex_count: usize = meshes.iter().map(|m| m.mesh.vertices.len()).sum();
+    write!(
+        out,
+        "ply\n\
+         format ascii 1.0\n\
+         comment fidget-koto\n\
+         element vertex {vertex_count}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         element face {}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n",
+        triangle_count(meshes)
+    )?;
+    for m in meshes {
+        let [r, g, b] = m.color_rgb;
+        for v in &m.mesh.vertices {
+            writeln!(out, "{} {} {} {r} {g} {b}", v.x, v.y, v.z)?;
+        }
+    }
+    let mut offset = 0;
+    for m in meshes {
+        for t in &m.mesh.triangles {
+            writeln!(out, "3 {} {} {}", t.x + offset, t.y + offset, t.z + offset)?;
+        }
+        offset += m.mesh.vertices.len();
+    }
+    Ok(())
+}
+
+/// Writes a 3MF file, with one named object per shape
+///
+/// Each object refers to a base material with the shape's color, which
+/// multi-material slicers map to filaments.  Coordinates are in millimeters.
+/// Shapes without triangles are left out, like in [`write_gltf`].
+pub fn write_3mf(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let parts = meshes
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| !m.mesh.triangles.is_empty())
+        .map(|(i, m)| (escape_xml(&m.part_name(i)), m))
+        .collect::<Vec<_>>();
+
+    let mut model = String::new();
+    model.push_str(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <model unit=\"millimeter\" xml:lang=\"en-US\" \
+         xmlns=\"http://schemas.microsoft.com/3dmanufacturing/core/2015/02\">\n\
+         <resources>\n",
+    );
+    // A material group needs at least one material
+    if !parts.is_empty() {
+        model.push_str("<basematerials id=\"1\">\n");
+        for (name, m) in &parts {
+            let [r, g, b] = m.color_rgb;
+            writeln!(
+                model,
+                "<base name=\"{name}\" displaycolor=\"#{r:02X}{g:02X}{b:02X}FF\"/>"
+            )
+            .unwrap();
+        }
+        model.push_str("</basematerials>\n");
+    }
+    for (i, (name, m)) in parts.iter().enumerate() {
+        writeln!(
+            model,
+            "<object id=\"{}\" type=\"model\" name=\"{name}\" pid=\"1\" pindex=\"{i}\">",
+            i + 2
+        )
+        .unwrap();
+        model.push_str("<mesh>\n<vertices>\n");
+        for v in &m.mesh.vertices {
+            writeln!(model, "<vertex x=\"{}\" y=\"{}\" z=\"{}\"/>", v.x, v.y, v.z).unwrap();
+        }
+        model.push_str("</vertices>\n<triangles>\n");
+        for t in &m.mesh.triangles {
+            writeln!(
+                model,
+                "<triangle v1=\"{}\" v2=\"{}\" v3=\"{}\"/>",
+                t.x, t.y, t.z
+            )
+            .unwrap();
+        }
+        model.push_str("</triangles>\n</mesh>\n</object>\n");
+    }
+    model.push_str("</resources>\n<build>\n");
+    for i in 0..parts.len() {
+        writeln!(model, "<item objectid=\"{}\"/>", i + 2).unwrap();
+    }
+    model.push_str("</build>\n</model>\n");
+
+    let content_types = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+        <Default Extension=\"rels\" \
+        ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+        <Default Extension=\"model\" \
+        ContentType=\"application/vnd.ms-package.3dmanufacturing-3dmodel+xml\"/>\
+        </Types>\n";
+    let rels = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+        <Relationship Target=\"/3D/3dmodel.model\" Id=\"rel0\" \
+        Type=\"http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel\"/>\
+        </Relationships>\n";
+    write_zip(
+        &[
+            ("[Content_Types].xml", content_types.as_bytes()),
+            ("_rels/.rels", rels.as_bytes()),
+            ("3D/3dmodel.model", model.as_bytes()),
+        ],
+        out,
+    )
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Writes an uncompressed ZIP archive, as used by 3MF
+fn write_zip(files: &[(&str, &[u8])], out: &mut impl Write) -> io::Result<()> {
+    // Fixed modification time, 1980-01-01 00:00, so output is reproducible
+    const TIME: u16 = 0;
+    const DATE: u16 = (1 << 5) | 1;
+
+    let mut central = vec![];
+    let mut offset = 0u32;
+    for (name, data) in files {
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let mut header = vec![];
+        header.extend(0x0403_4b50u32.to_le_bytes());
+        for field in [20u16, 0, 0, TIME, DATE] {
+            header.extend(field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            header.extend(field.to_le_bytes());
+        }
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(name.as_bytes());
+        out.write_all(&header)?;
+        out.write_all(data)?;
+
+        central.extend(0x0201_4b50u32.to_le_bytes());
+        for field in [20u16, 20, 0, 0, TIME, DATE] {
+            central.extend(field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            central.extend(field.to_le_bytes());
+        }
+        for field in [name.len() as u16, 0, 0, 0, 0] {
+            central.extend(field.to_le_bytes());
+        }
+        central.extend(0u32.to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+
+        offset += header.len() as u32 + size;
+    }
+    out.write_all(&central)?;
+
+    let mut end = vec![];
+    end.extend(0x0605_4b50u32.to_le_bytes());
+    let count = files.len() as u16;
+    for field in [0u16, 0, count, count] {
+        end.extend(field.to_le_bytes());
+    }
+    end.extend((central.len() as u32).to_le_bytes());
+    end.extend(offset.to_le_bytes());
+    end.extend(0u16.to_le_bytes());
+    out.write_all(&end)
+}
+
+/// Converts an sRGB channel to linear, as glTF colors are linear
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Builds the glTF JSON and binary buffer
+///
+/// If `embed` is set, the buffer is embedded in the JSON as a data URI.
+fn gltf(meshes: &[ShapeMesh], embed: bool) -> (String, Vec<u8>) {
+    let mut buffer: Vec<u8> = vec![];
+    let mut nodes = vec![];
+    let mut gltf_meshes = vec![];
+    let mut materials = vec![];
+    let mut views = vec![];
+    let mut accessors = vec![];
+    // glTF doesn't allow empty accessors, so shapes without triangles are
+    // left out
+    for (i, m) in meshes
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| !m.mesh.triangles.is_empty())
+    {
+        let name = escape_json(&m.part_name(i));
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        let positions = buffer.len();
+        for v in &m.mesh.vertices {
+            for (j, c) in [v.x, v.y, v.z].into_iter().enumerate() {
+                min[j] = min[j].min(c);
+                max[j] = max[j].max(c);
+                buffer.extend(c.to_le_bytes());
+            }
+        }
+        let indices = buffer.len();
+        for t in &m.mesh.triangles {
+            for i in [t.x, t.y, t.z] {
+                buffer.extend((i as u32).to_le_bytes());
+            }
+        }
+
+        let view = views.len();
+        views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{positions},\"byteLength\":{},\"target\":34962}}",
+            indices - positions
+        ));
+        views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{indices},\"byteLength\":{},\"target\":34963}}",
+            buffer.len() - indices
+        ));
+        let accessor = accessors.len();
+        accessors.push(format!(
+            "{{\"bufferView\":{view},\"componentType\":5126,\"count\":{},\"type\":\"VEC3\",\
+             \"min\":[{},{},{}],\"max\":[{},{},{}]}}",
+            m.mesh.vertices.len(),
+            min[0],
+            min[1],
+            min[2],
+            max[0],
+            max[1],
+            max[2]
+        ));
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}",
+            view + 1,
+            m.mesh.triangles.len() * 3
+        ));
+        let [r, g, b] = m.color_rgb.map(srgb_to_linear);
+        let material = materials.len();
+        materials.push(format!(
+            "{{\"name\":\"{name}\",\"pbrMetallicRoughness\":\
+             {{\"baseColorFactor\":[{r},{g},{b},1],\"metallicFactor\":0,\"roughnessFactor\":1}}}}"
+        ));
+        let mesh = gltf_meshes.len();
+        gltf_meshes.push(format!(
+            "{{\"name\":\"{name}\",\"primitives\":[{{\"attributes\":{{\"POSITION\":{accessor}}},\
+             \"indices\":{},\"material\":{material}}}]}}",
+            accessor + 1
+        ));
+        nodes.push(format!("{{\"name\":\"{name}\",\"mesh\":{mesh}}}"));
+    }
+
+    let uri = if embed {
+        format!(
+            ",\"uri\":\"data:application/octet-stream;base64,{}\"",
+            base64(&buffer)
+        )
+    } else {
+        String::new()
+    };
+    let mut json =
+        String::from("{\"asset\":{\"version\":\"2.0\",\"generator\":\"fidget-koto\"},\"scene\":0");
+    // Top-level arrays must not be empty, so a file without meshes has a
+    // single empty scene
+    if nodes.is_empty() {
+        json.push_str(",\"scenes\":[{}]");
+    } else {
+        let scene_nodes = (0..nodes.len())
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            json,
+            ",\"scenes\":[{{\"nodes\":[{scene_nodes}]}}],\
+             \"nodes\":[{}],\"meshes\":[{}],\"materials\":[{}],\
+             \"buffers\":[{{\"byteLength\":{}{uri}}}],\"bufferViews\":[{}],\"accessors\":[{}]",
+            nodes.join(","),
+            gltf_meshes.join(","),
+            materials.join(","),
+            buffer.len(),
+            views.join(","),
+            accessors.join(",")
+        )
+        .unwrap();
+    }
+    json.push('}');
+    (json, buffer)
+}
+
+/// Writes a glTF file with the mesh data embedded, one named mesh per shape
+///
+/// Each mesh has a material with the shape's color as its base color.
+pub fn write_gltf(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let (json, _buffer) = gltf(meshes, true);
+    out.write_all(json.as_bytes())
+}
+
+/// Writes a binary glTF (GLB) file, see [`write_gltf`]
+pub fn write_glb(meshes: &[ShapeMesh], out: &mut impl Write) -> io::Result<()> {
+    let (json, mut buffer) = gltf(meshes, false);
+    // Chunks are 4-byte aligned, with JSON padded by spaces
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+    let mut length = 12 + 8 + json.len();
+    if !buffer.is_empty() {
+        length += 8 + buffer.len();
+    }
+    out.write_all(b"glTF")?;
+    out.write_all(&2u32.to_le_bytes())?;
+    out.write_all(&(length as u32).to_le_bytes())?;
+    out.write_all(&(json.len() as u32).to_le_bytes())?;
+    out.write_all(b"JSON")?;
+    out.write_all(&json)?;
+    if !buffer.is_empty() {
+        out.write_all(&(buffer.len() as u32).to_le_bytes())?;
+        out.write_all(b"BIN\0")?;
+        out.write_all(&buffer)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Procedural noise fields built from [`Tree`] operations
+//!
+//! The noise functions only use arithmetic, `floor`, `sin`, `min`, `max` and
+//! `compare`, so the results can be evaluated with interval arithmetic like
+//! any other shape.  Hashing is done with the usual `fract(sin(...))` shader
+//! trick, so the noise is deterministic for a given seed.
+//!
+//! In Koto, the noise functions are available at the top level:
+//!
+//! ```koto
+//! draw (sphere 1).tree() + fbm3(x * 4, y * 4, z * 4, 7, 4) * 0.05
+//! ```
+
+use crate::KTree;
+use crate::args::{Param, parse_args};
+use crate::math::ShaderMath;
+use fidget::context::Tree;
+use koto::prelude::*;
+
+/// Point in space, with [`Tree`] coordinates
+type Point = [Tree; 3];
+
+/// Hashing weights, one set per hashed output
+const HASH_WEIGHTS: [[f64; 4]; 3] = [
+    [127.1, 311.7, 74.7, 113.5],
+    [269.5, 183.3, 246.1, 271.9],
+    [113.5, 271.9, 124.6, 307.3],
+];
+
+/// Hashes a lattice point into a value in `[0, 1)`
+fn hash(p: &Point, seed: f64, weights: [f64; 4]) -> Tree {
+    let dot = p[0].clone() * weights[0] + p[1].clone() * weights[1] + p[2].clone() * weights[2];
+    ((dot + seed * weights[3]).sin() * 43758.5453).fract()
+}
+
+/// Hashes a lattice point into a gradient vector with components in `[-1, 1)`
+fn hash_gradient(p: &Point, seed: f64) -> Point {
+    HASH_WEIGHTS.map(|w| hash(p, seed, w) * 2.0 - 1.0)
+}
+
+fn dot(a: &Point, b: &Point) -> Tree {
+    a[0].clone() * b[0].clone() + a[1].clone() * b[1].clone() + a[2].clone() * b[2].clone()
+}
+
+/// Interpolates the eight corners of a cell, indexed as `x + 2 * y + 4 * z`
+fn trilinear(corners: [Tree; 8], u: &Point) -> Tree {
+    let [c000, c100, c010, c110, c001, c101, c011, c111] = corners;
+    let x00 = c000.mix(c100, u[0].clone());
+    let x10 = c010.mix(c110, u[0].clone());
+    let x01 = c001.mix(c101, u[0].clone());
+    let x11 = c011.mix(c111, u[0].clone());
+    let y0 = x00.mix(x10, u[1].clone());
+    let y1 = x01.mix(x11, u[1].clone());
+    y0.mix(y1, u[2].clone())
+}
+
+/// Splits a point into its cell corner and the offset within that cell
+fn cell(x: Tree, y: Tree, z: Tree) -> (Point, Point) {
+    let i = [x.clone().floor(), y.clone().floor(), z.clone().floor()];
+    let f = [x - i[0].clone(), y - i[1].clone(), z - i[2].clone()];
+    (i, f)
+}
+
+/// Returns a corner of the cell at `i`, with `offset` being 0 or 1 per axis
+fn corner(i: &Point, offset: [f64; 3]) -> Point {
+    [
+        i[0].clone() + offset[0],
+        i[1].clone() + offset[1],
+        i[2].clone() + offset[2],
+    ]
+}
+
+const CORNERS: [[f64; 3]; 8] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 0.0, 1.0],
+    [0.0, 1.0, 1.0],
+    [1.0, 1.0, 1.0],
+];
+
+/// Value noise in `[-1, 1]`, with a feature size of 1
+pub fn value_noise3(x: Tree, y: Tree, z: Tree, seed: f64) -> Tree {
+    let (i, f) = cell(x, y, z);
+    // Cubic smoothstep
+    let u = f.map(|f| f.clone() * f.clone() * (f * -2.0 + 3.0));
+    let corners = CORNERS.map(|c| hash(&corner(&i, c), seed, HASH_WEIGHTS[0]));
+    trilinear(corners, &u) * 2.0 - 1.0
+}
+
+/// Gradient (Perlin-style) noise in roughly `[-1, 1]`, with a feature size of 1
+pub fn gradient_noise3(x: Tree, y: Tree, z: Tree, seed: f64) -> Tree {
+    let (i, f) = cell(x, y, z);
+    // Quintic fade curve, which is C2-continuous across cells
+    let u = f
+        .clone()
+        .map(|f| f.clone() * f.clone() * f.clone() * (f.clone() * (f * 6.0 - 15.0) + 10.0));
+    let corners = CORNERS.map(|c| {
+        let g = hash_gradient(&corner(&i, c), seed);
+        let d = [
+            f[0].clone() - c[0],
+            f[1].clone() - c[1],
+            f[2].clone() - c[2],
+        ];
+        dot(&g, &d)
+    });
+    trilinear(corners, &u)
+}
+
+/// Simplex-style noise in roughly `[-1, 1]`, with a feature size of 1
+///
+/// This samples four corners of a simplex instead of eight corners of a cube,
+/// which gives fewer axis-aligned artifacts and a smaller tree than
+/// [`gradient_noise3`].
+pub fn simplex_noise3(x: Tree, y: Tree, z: Tree, seed: f64) -> Tree {
+    const F: f64 = 1.0 / 3.0;
+    const G: f64 = 1.0 / 6.0;
+
+    // Skew the input space to find the simplex cell
+    let s = (x.clone() + y.clone() + z.clone()) * F;
+    let i = [
+        (x.clone() + s.clone()).floor(),
+        (y.clone() + s.clone()).floor(),
+        (z.clone() + s).floor(),
+    ];
+    let t = (i[0].clone() + i[1].clone() + i[2].clone()) * G;
+    let x0 = [
+        x - i[0].clone() + t.clone(),
+        y - i[1].clone() + t.clone(),
+        z - i[2].clone() + t,
+    ];
+
+    // Find which simplex we are in, without branching: g[k] is 1 if
+    // x0[k] >= x0[k + 1 (mod 3)], else 0
+    let g = [
+        x0[0].step(x0[1].clone()),
+        x0[1].step(x0[2].clone()),
+        x0[2].step(x0[0].clone()),
+    ];
+    let l = g.clone().map(|g| -g + 1.0);
+    let i1 = [
+        g[0].clone().min(l[2].clone()),
+        g[1].clone().min(l[0].clone()),
+        g[2].clone().min(l[1].clone()),
+    ];
+    let i2 = [
+        g[0].clone().max(l[2].clone()),
+        g[1].clone().max(l[0].clone()),
+        g[2].clone().max(l[1].clone()),
+    ];
+
+    let offset = |p: &Point, o: &Point, c: f64| -> Point {
+        [
+            p[0].clone() - o[0].clone() + c,
+            p[1].clone() - o[1].clone() + c,
+            p[2].clone() - o[2].clone() + c,
+        ]
+    };
+    let zero = [0.0; 3].map(Tree::constant);
+    let one = [1.0; 3].map(Tree::constant);
+    let x1 = offset(&x0, &i1, G);
+    let x2 = offset(&x0, &i2, 2.0 * G);
+    let x3 = offset(&x0, &one, 3.0 * G);
+
+    let corner_offsets = [zero, i1, i2, one];
+    [x0, x1, x2, x3]
+        .iter()
+        .zip(corner_offsets.iter())
+        .map(|(d, o)| {
+            let c = [
+                i[0].clone() + o[0].clone(),
+                i[1].clone() + o[1].clone(),
+                i[2].clone() + o[2].clone(),
+            ];
+            let m = (-dot(d, d) + 0.6).max(0.0).square().square();
+            m * dot(&hash_gradient(&c, seed), d)
+        })
+        .reduce(|a, b| a + b)
+        .unwrap()
+        * 32.0
+}
+
+/// Fractal Brownian motion, summing `octaves` layers of [`gradient_noise3`]
+///
+/// Each octave scales the frequency by `lacunarity` and the amplitude by
+/// `gain`; the result is normalized to roughly `[-1, 1]`.
+pub fn fbm3(
+    x: Tree,
+    y: Tree,
+    z: Tree,
+    seed: f64,
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,
+) -> Tree {
+    let mut sum = Tree::constant(0.0);
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    for octave in 0..octaves.max(1) {
+        let noise = gradient_noise3(
+            x.clone() * frequency,
+            y.clone() * frequency,
+            z.clone() * frequency,
+            seed + octave as f64,
+        );
+        sum = sum + noise * amplitude;
+        total += amplitude;
+        frequency *= lacunarity;
+        amplitude *= gain;
+    }
+    sum / total
+}
+
+/// Adds the noise functions to the given module
+pub(crate) fn add_noise_fns(module: &KMap) {
+    macro_rules! add_noise_fn {
+        ($name_string:literal, $name:ident) => {
+            module.add_fn($name_string, move |ctx| {
+                let args = parse_args(
+                    ctx.args(),
+                    &[&[Param::fields3("p"), Param::number("seed").or_number(0.0)]],
+                )?;
+                let [x, y, z] = args.fields3(0);
+                Ok(KTree::from($name(x, y, z, args.number(1))).into())
+            });
+        };
+    }
+
+    add_noise_fn!("value_noise3", value_noise3);
+    add_noise_fn!("noise3", gradient_noise3);
+    add_noise_fn!("simplex3", simplex_noise3);
+
+    module.add_fn("fbm3", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::fields3("p"),
+                Param::number("seed"),
+                Param::number("octaves"),
+                Param::number("lacunarity").or_number(2.0),
+                Param::number("gain").or_number(0.5),
+            ]],
+        )?;
+        let [x, y, z] = args.fields3(0);
+        let octaves = args.number(2).max(1.0) as usize;
+        let result = fbm3(
+            x,
+            y,
+            z,
+            args.number(1),
+            octaves,
+            args.number(3),
+            args.number(4),
+        );
+        Ok(KTree::from(result).into())
+    });
+}
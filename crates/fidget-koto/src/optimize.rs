@@ -0,0 +1,260 @@
+//! Optimization of [`Tree`]s before rendering
+//!
+//! All trees are imported into a single [`Context`], which deduplicates
+//! identical subtrees, both within and across trees.  Every node is then
+//! rebuilt bottom-up with constant folding and algebraic simplification:
+//!
+//! - operations on constants are evaluated (except for `round`, `not`,
+//!   `mod`, `and` and `or`, whose exact semantics are left to fidget)
+//! - `a + 0`, `a - 0`, `a * 1`, `a / 1` become `a`; `0 - a`, `a * -1` and
+//!   `a / -1` become `-a`; `a * 0` becomes `0`; `a - a` becomes `0`
+//! - `min(a, a)` and `max(a, a)` become `a`
+//! - `--a` becomes `a`, `abs(abs(a))` and `abs(-a)` become `abs(a)`, and
+//!   `square(-a)` becomes `square(a)`
+//!
+//! The rewrites assume finite values: `a * 0` and `a - a` are `NaN` rather
+//! than `0` if `a` is infinite.
+
+use crate::inspect::{children, post_order};
+use fidget::{
+    Error,
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Node counts before and after optimization
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OptimizeStats {
+    /// Number of nodes with shared subtrees written out every time they are
+    /// used, i.e. the size of the trees without any sharing
+    pub expanded: u64,
+    /// Number of unique nodes, deduplicated within each tree
+    pub before: usize,
+    /// Number of unique nodes after optimization, deduplicated across trees
+    pub after: usize,
+}
+
+impl fmt::Display for OptimizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} expanded) before optimization, {} after",
+            self.before, self.expanded, self.after
+        )
+    }
+}
+
+/// Optimizes a set of trees, returning the new trees in the same order
+pub fn optimize(trees: &[Tree]) -> Result<(Vec<Tree>, OptimizeStats), Error> {
+    let mut ctx = Context::new();
+    let mut stats = OptimizeStats::default();
+    let mut memo: HashMap<Node, Node> = HashMap::new();
+    let mut roots = vec![];
+    for tree in trees {
+        let root = ctx.import(tree);
+        let order = post_order(&ctx, root);
+        stats.before += order.len();
+
+        let mut sizes: HashMap<Node, u64> = HashMap::new();
+        for node in order {
+            let op = ctx.get_op(node).ok_or(Error::BadNode)?.clone();
+            let size = children(&op)
+                .iter()
+                .fold(1u64, |acc, c| acc.saturating_add(sizes[c]));
+            sizes.insert(node, size);
+            if !memo.contains_key(&node) {
+                let new = simplify(&mut ctx, node, &op, &memo)?;
+                memo.insert(node, new);
+            }
+        }
+        stats.expanded = stats.expanded.saturating_add(sizes[&root]);
+        roots.push(memo[&root]);
+    }
+
+    let mut seen = HashSet::new();
+    for root in &roots {
+        seen.extend(post_order(&ctx, *root));
+    }
+    stats.after = seen.len();
+
+    let trees = roots
+        .into_iter()
+        .map(|root| ctx.export(root))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((trees, stats))
+}
+
+/// Replaces variables with constant values, then optimizes the tree
+///
+/// Hosts which can't bind variables at evaluation time (e.g. the mesher) use
+/// this to substitute the values of variables such as `t`.
+pub fn bind_vars(tree: &Tree, values: &[(Var, f64)]) -> Result<Tree, Error> {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+    let mut memo: HashMap<Node, Node> = HashMap::new();
+    for node in post_order(&ctx, root) {
+        let op = ctx.get_op(node).ok_or(Error::BadNode)?.clone();
+        let new = match op {
+            Op::Input(var) => match values.iter().find(|(v, _)| *v == var) {
+                Some((_, value)) => ctx.constant(*value),
+                None => node,
+            },
+            op => simplify(&mut ctx, node, &op, &memo)?,
+        };
+        memo.insert(node, new);
+    }
+    ctx.export(memo[&root])
+}
+
+fn constant(ctx: &Context, node: Node) -> Option<f64> {
+    match ctx.get_op(node)? {
+        Op::Const(c) => Some(c.0),
+        _ => None,
+    }
+}
+
+/// Rebuilds `node`, whose children have already been rebuilt into `memo`
+fn simplify(
+    ctx: &mut Context,
+    node: Node,
+    op: &Op,
+    memo: &HashMap<Node, Node>,
+) -> Result<Node, Error> {
+    match op {
+        Op::Input(_) | Op::Const(_) => Ok(node),
+        Op::Unary(opcode, a) => simplify_unary(ctx, *opcode, memo[a]),
+        Op::Binary(opcode, a, b) => simplify_binary(ctx, *opcode, memo[a], memo[b]),
+    }
+}
+
+fn simplify_unary(ctx: &mut Context, opcode: UnaryOpcode, a: Node) -> Result<Node, Error> {
+    if let Some(v) = constant(ctx, a).and_then(|v| fold_unary(opcode, v)) {
+        return Ok(ctx.constant(v));
+    }
+    match (opcode, ctx.get_op(a).cloned()) {
+        (UnaryOpcode::Neg, Some(Op::Unary(UnaryOpcode::Neg, inner))) => Ok(inner),
+        (UnaryOpcode::Abs, Some(Op::Unary(UnaryOpcode::Abs, _))) => Ok(a),
+        (UnaryOpcode::Abs, Some(Op::Unary(UnaryOpcode::Neg, inner))) => ctx.abs(inner),
+        (UnaryOpcode::Square, Some(Op::Unary(UnaryOpcode::Neg, inner))) => ctx.square(inner),
+        _ => apply_unary(ctx, opcode, a),
+    }
+}
+
+fn simplify_binary(
+    ctx: &mut Context,
+    opcode: BinaryOpcode,
+    a: Node,
+    b: Node,
+) -> Result<Node, Error> {
+    let (ca, cb) = (constant(ctx, a), constant(ctx, b));
+    if let Some(v) = ca.zip(cb).and_then(|(ca, cb)| fold_binary(opcode, ca, cb)) {
+        return Ok(ctx.constant(v));
+    }
+    match (opcode, ca, cb) {
+        (BinaryOpcode::Add, _, Some(0.0)) => Ok(a),
+        (BinaryOpcode::Add, Some(0.0), _) => Ok(b),
+        (BinaryOpcode::Sub, _, Some(0.0)) => Ok(a),
+        (BinaryOpcode::Sub, Some(0.0), _) => simplify_unary(ctx, UnaryOpcode::Neg, b),
+        (BinaryOpcode::Sub, ..) if a == b => Ok(ctx.constant(0.0)),
+        (BinaryOpcode::Mul, _, Some(1.0)) | (BinaryOpcode::Div, _, Some(1.0)) => Ok(a),
+        (BinaryOpcode::Mul, Some(1.0), _) => Ok(b),
+        (BinaryOpcode::Mul, Some(0.0), _) | (BinaryOpcode::Mul, _, Some(0.0)) => {
+            Ok(ctx.constant(0.0))
+        }
+        (BinaryOpcode::Mul, _, Some(-1.0)) | (BinaryOpcode::Div, _, Some(-1.0)) => {
+            simplify_unary(ctx, UnaryOpcode::Neg, a)
+        }
+        (BinaryOpcode::Mul, Some(-1.0), _) => simplify_unary(ctx, UnaryOpcode::Neg, b),
+        (BinaryOpcode::Min | BinaryOpcode::Max, ..) if a == b => Ok(a),
+        _ => apply_binary(ctx, opcode, a, b),
+    }
+}
+
+fn fold_unary(opcode: UnaryOpcode, v: f64) -> Option<f64> {
+    Some(match opcode {
+        UnaryOpcode::Neg => -v,
+        UnaryOpcode::Abs => v.abs(),
+        UnaryOpcode::Recip => 1.0 / v,
+        UnaryOpcode::Sqrt => v.sqrt(),
+        UnaryOpcode::Square => v * v,
+        UnaryOpcode::Floor => v.floor(),
+        UnaryOpcode::Ceil => v.ceil(),
+        UnaryOpcode::Sin => v.sin(),
+        UnaryOpcode::Cos => v.cos(),
+        UnaryOpcode::Tan => v.tan(),
+        UnaryOpcode::Asin => v.asin(),
+        UnaryOpcode::Acos => v.acos(),
+        UnaryOpcode::Atan => v.atan(),
+        UnaryOpcode::Exp => v.exp(),
+        UnaryOpcode::Ln => v.ln(),
+        UnaryOpcode::Round | UnaryOpcode::Not => return None,
+    })
+}
+
+fn fold_binary(opcode: BinaryOpcode, a: f64, b: f64) -> Option<f64> {
+    // fidget propagates NaN through min, max and compare, unlike f64::min
+    let nan = a.is_nan() || b.is_nan();
+    Some(match opcode {
+        BinaryOpcode::Add => a + b,
+        BinaryOpcode::Sub => a - b,
+        BinaryOpcode::Mul => a * b,
+        BinaryOpcode::Div => a / b,
+        BinaryOpcode::Atan => a.atan2(b),
+        BinaryOpcode::Min if nan => f64::NAN,
+        BinaryOpcode::Min => a.min(b),
+        BinaryOpcode::Max if nan => f64::NAN,
+        BinaryOpcode::Max => a.max(b),
+        BinaryOpcode::Compare if nan => f64::NAN,
+        BinaryOpcode::Compare => match a.partial_cmp(&b)? {
+            std::cmp::Ordering::Less => -1.0,
+            std::cmp::Ordering::Equal => 0.0,
+            std::cmp::Ordering::Greater => 1.0,
+        },
+        BinaryOpcode::Mod | BinaryOpcode::And | BinaryOpcode::Or => return None,
+    })
+}
+
+pub(crate) fn apply_unary(ctx: &mut Context, opcode: UnaryOpcode, a: Node) -> Result<Node, Error> {
+    match opcode {
+        UnaryOpcode::Neg => ctx.neg(a),
+        UnaryOpcode::Abs => ctx.abs(a),
+        UnaryOpcode::Recip => ctx.recip(a),
+        UnaryOpcode::Sqrt => ctx.sqrt(a),
+        UnaryOpcode::Square => ctx.square(a),
+        UnaryOpcode::Floor => ctx.floor(a),
+        UnaryOpcode::Ceil => ctx.ceil(a),
+        UnaryOpcode::Round => ctx.round(a),
+        UnaryOpcode::Sin => ctx.sin(a),
+        UnaryOpcode::Cos => ctx.cos(a),
+        UnaryOpcode::Tan => ctx.tan(a),
+        UnaryOpcode::Asin => ctx.asin(a),
+        UnaryOpcode::Acos => ctx.acos(a),
+        UnaryOpcode::Atan => ctx.atan(a),
+        UnaryOpcode::Exp => ctx.exp(a),
+        UnaryOpcode::Ln => ctx.ln(a),
+        UnaryOpcode::Not => ctx.not(a),
+    }
+}
+
+pub(crate) fn apply_binary(
+    ctx: &mut Context,
+    opcode: BinaryOpcode,
+    a: Node,
+    b: Node,
+) -> Result<Node, Error> {
+    match opcode {
+        BinaryOpcode::Add => ctx.add(a, b),
+        BinaryOpcode::Sub => ctx.sub(a, b),
+        BinaryOpcode::Mul => ctx.mul(a, b),
+        BinaryOpcode::Div => ctx.div(a, b),
+        BinaryOpcode::Atan => ctx.atan2(a, b),
+        BinaryOpcode::Min => ctx.min(a, b),
+        BinaryOpcode::Max => ctx.max(a, b),
+        BinaryOpcode::Compare => ctx.compare(a, b),
+        BinaryOpcode::Mod => ctx.modulo(a, b),
+        BinaryOpcode::And => ctx.and(a, b),
+        BinaryOpcode::Or => ctx.or(a, b),
+    }
+}
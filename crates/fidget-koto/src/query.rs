@@ -0,0 +1,127 @@
+//! Evaluating trees from inside scripts
+//!
+//! These functions build a VM tape for the tree on every call.  The VM is
+//! used instead of the JIT, because compiling a JIT function costs far more
+//! than evaluating a single point or interval.
+//!
+//! In Koto, `Tree` has `eval`, `interval` and `gradient` methods.  Variables
+//! declared with `var(name)` are replaced by their defaults first, as for
+//! [`DrawShape::bind_defaults`](crate::DrawShape::bind_defaults):
+//!
+//! ```koto
+//! s = (sphere 1).tree()
+//! print s.eval 0, 0, 0           # -1
+//! print s.interval [0, 2], [0, 1], [0, 1]
+//! print s.gradient [2, 0, 0]     # Vec3(1, 0, 0)
+//! ```
+
+use crate::args::{Param, parse_args};
+use crate::engine::running_vars;
+use crate::optimize::bind_vars;
+use crate::utils::numbers_to_vec3;
+use fidget::{
+    context::Tree,
+    shape::EzShape,
+    types::{Grad, Interval},
+    vm::VmShape,
+};
+use koto::{prelude::*, runtime, runtime::runtime_error};
+
+/// Evaluates the tree at a single point
+pub fn point(tree: Tree, x: f64, y: f64, z: f64) -> Result<f64, fidget::Error> {
+    let shape = VmShape::from(tree);
+    let mut eval = VmShape::new_point_eval();
+    let tape = shape.ez_point_tape();
+    let (value, _trace) = eval.eval(&tape, x as f32, y as f32, z as f32)?;
+    Ok(value as f64)
+}
+
+/// Evaluates the tree over a box, returning bounds `[lower, upper]`
+///
+/// The bounds are conservative: every value in the box lies within them, but
+/// they may be wider than the actual range.
+pub fn interval(
+    tree: Tree,
+    x: [f64; 2],
+    y: [f64; 2],
+    z: [f64; 2],
+) -> Result<[f64; 2], fidget::Error> {
+    let to_interval = |[a, b]: [f64; 2]| Interval::new(a.min(b) as f32, a.max(b) as f32);
+    let shape = VmShape::from(tree);
+    let mut eval = VmShape::new_interval_eval();
+    let tape = shape.ez_interval_tape();
+    let (value, _trace) = eval.eval(&tape, to_interval(x), to_interval(y), to_interval(z))?;
+    Ok([value.lower() as f64, value.upper() as f64])
+}
+
+/// Evaluates the tree and its partial derivatives at a single point
+///
+/// Returns `[value, dx, dy, dz]`.
+pub fn gradient(tree: Tree, x: f64, y: f64, z: f64) -> Result<[f64; 4], fidget::Error> {
+    let shape = VmShape::from(tree);
+    let mut eval = VmShape::new_grad_slice_eval();
+    let tape = shape.ez_grad_slice_tape();
+    let out = eval.eval(
+        &tape,
+        &[Grad::new(x as f32, 1.0, 0.0, 0.0)],
+        &[Grad::new(y as f32, 0.0, 1.0, 0.0)],
+        &[Grad::new(z as f32, 0.0, 0.0, 1.0)],
+    )?;
+    let g = out[0];
+    Ok([g.v, g.dx, g.dy, g.dz].map(f64::from))
+}
+
+/// Replaces the running engine's variables by their defaults
+fn bind_defaults(tree: Tree) -> runtime::Result<Tree> {
+    let values = running_vars()
+        .iter()
+        .map(|v| (v.var, v.default))
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return Ok(tree);
+    }
+    match bind_vars(&tree, &values) {
+        Ok(tree) => Ok(tree),
+        Err(err) => runtime_error!("binding variables failed: {err}"),
+    }
+}
+
+/// Implementation of the `eval` method: `|p: Vec3| -> Number`
+pub(crate) fn eval_method(tree: Tree, args: &[KValue]) -> runtime::Result<KValue> {
+    let args = parse_args(args, &[&[Param::vec3("p")]])?;
+    let tree = bind_defaults(tree)?;
+    let [x, y, z] = args.vec3(0);
+    match point(tree, x, y, z) {
+        Ok(value) => Ok(value.into()),
+        Err(err) => runtime_error!("eval failed: {err}"),
+    }
+}
+
+/// Implementation of the `interval` method:
+/// `|x: Vec2, y: Vec2, [z: Vec2]| -> (Number, Number)`
+pub(crate) fn interval_method(tree: Tree, args: &[KValue]) -> runtime::Result<KValue> {
+    let args = parse_args(
+        args,
+        &[&[
+            Param::vec2("x"),
+            Param::vec2("y"),
+            Param::vec2("z").or_vector([0.0; 3]),
+        ]],
+    )?;
+    let tree = bind_defaults(tree)?;
+    match interval(tree, args.vec2(0), args.vec2(1), args.vec2(2)) {
+        Ok([lower, upper]) => Ok(KValue::Tuple(vec![lower.into(), upper.into()].into())),
+        Err(err) => runtime_error!("interval evaluation failed: {err}"),
+    }
+}
+
+/// Implementation of the `gradient` method: `|p: Vec3| -> Vec3`
+pub(crate) fn gradient_method(tree: Tree, args: &[KValue]) -> runtime::Result<KValue> {
+    let args = parse_args(args, &[&[Param::vec3("p")]])?;
+    let [x, y, z] = args.vec3(0);
+    let tree = bind_defaults(tree)?;
+    match gradient(tree, x, y, z) {
+        Ok([_, dx, dy, dz]) => Ok(numbers_to_vec3(dx, dy, dz)),
+        Err(err) => runtime_error!("gradient evaluation failed: {err}"),
+    }
+}
@@ -0,0 +1,316 @@
+//! CPU rendering of a [`ScriptContext`] to RGBA images, without a window or
+//! GPU:
+//!
+//! ```no_run
+//! use fidget::vm::VmFunction;
+//! use fidget_koto::{Engine, render};
+//!
+//! let out = Engine::default().run("draw sphere 0.5")?;
+//! let image = render::render_3d::<VmFunction>(&out, render::Mode3D::Shaded, 256, 256)?;
+//! assert_eq!(image.len(), 256 * 256);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Pixels are stored row by row, starting at the top left.  Shapes are seen
+//! through the context's [`view`](ScriptContext::view) hints, and variables
+//! other than `x`, `y` and `z` are replaced by their defaults.  Empty pixels
+//! are transparent.
+//!
+//! Interactive hosts can build the same layers with [`layers_2d`] and
+//! [`layers_3d`], then render each one with their own camera using
+//! [`render_layer_2d`] and [`render_layer_3d`].
+
+use crate::ScriptContext;
+use fidget::{
+    context::Tree,
+    eval::{Function, MathFunction},
+    render::{
+        BitRenderMode, DebugRenderMode, GeometryPixel, ImageRenderConfig, ImageSize, RenderHints,
+        SdfPixelRenderMode, SdfRenderMode, View2, View3, VoxelRenderConfig, VoxelSize,
+    },
+    shape::Shape,
+};
+use std::fmt;
+use std::str::FromStr;
+
+/// Rendering mode for 2D images
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Mode2D {
+    /// Filled shapes in their own colors, drawn in order
+    #[default]
+    Color,
+    /// Distance field of all shapes, with fidget's SDF coloring
+    Sdf,
+    /// Like [`Sdf`](Self::Sdf), but evaluated at every pixel
+    ExactSdf,
+    /// Tile and interval evaluation debug colors for all shapes
+    Debug,
+}
+
+/// Rendering mode for 3D images
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Mode3D {
+    /// Grayscale depth
+    Heightmap,
+    /// Absolute value of the surface normals as RGB
+    Normals,
+    /// Lit surfaces in the shapes' colors
+    #[default]
+    Shaded,
+}
+
+impl Mode2D {
+    /// Every mode
+    pub const ALL: [Self; 4] = [Self::Color, Self::Sdf, Self::ExactSdf, Self::Debug];
+
+    /// Returns the name of the mode, as parsed by [`FromStr`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::Sdf => "sdf",
+            Self::ExactSdf => "exact-sdf",
+            Self::Debug => "debug",
+        }
+    }
+}
+
+impl Mode3D {
+    /// Every mode
+    pub const ALL: [Self; 3] = [Self::Heightmap, Self::Normals, Self::Shaded];
+
+    /// Returns the name of the mode, as parsed by [`FromStr`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Heightmap => "heightmap",
+            Self::Normals => "normals",
+            Self::Shaded => "shaded",
+        }
+    }
+}
+
+impl fmt::Display for Mode2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Mode3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Mode2D {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| format!("unknown 2D mode '{s}'"))
+    }
+}
+
+impl FromStr for Mode3D {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| format!("unknown 3D mode '{s}'"))
+    }
+}
+
+/// Returns the trees to draw in 2D, with their colors, bottom to top
+///
+/// In [`Mode2D::Color`], every shape is its own layer.  The other modes give
+/// a single layer with the union of all shapes, since their images are
+/// opaque.  Trees are in model coordinates, to be drawn with
+/// [`ViewHints::view_2d`](crate::view::ViewHints::view_2d) or a host's
+/// camera.
+pub fn layers_2d(ctx: &ScriptContext, mode: Mode2D) -> Result<Vec<(Tree, [u8; 3])>, fidget::Error> {
+    let mut layers = ctx
+        .shapes
+        .iter()
+        .map(|s| Ok((s.bind_defaults()?, s.color_rgb)))
+        .collect::<Result<Vec<_>, fidget::Error>>()?;
+    if mode == Mode2D::Color {
+        return Ok(layers);
+    }
+    let union = layers.pop().map(|(last, color)| {
+        let union = layers.into_iter().fold(last, |acc, (t, _)| t.min(acc));
+        (union, color)
+    });
+    Ok(union.into_iter().collect())
+}
+
+/// Returns the trees to draw in 3D, with their colors
+///
+/// Every shape is its own layer, oriented by
+/// [`ViewHints::orient_3d`](crate::view::ViewHints::orient_3d) so that it
+/// can be drawn with [`ViewHints::view_3d`](crate::view::ViewHints::view_3d)
+/// or a host's camera.
+pub fn layers_3d(ctx: &ScriptContext) -> Result<Vec<(Tree, [u8; 3])>, fidget::Error> {
+    ctx.shapes
+        .iter()
+        .map(|s| Ok((ctx.view.orient_3d(&s.bind_defaults()?), s.color_rgb)))
+        .collect()
+}
+
+/// Renders a single layer from [`layers_2d`]
+pub fn render_layer_2d<F: Function + MathFunction + RenderHints>(
+    mode: Mode2D,
+    tree: Tree,
+    view: View2,
+    image_size: ImageSize,
+    color: [u8; 3],
+) -> Vec<[u8; 4]> {
+    let config = ImageRenderConfig {
+        image_size,
+        tile_sizes: F::tile_sizes_2d(),
+        view,
+        ..Default::default()
+    };
+    let shape = Shape::<F>::from(tree);
+
+    // `None` means the render was cancelled, which we never do
+    let out = match mode {
+        Mode2D::Color => {
+            let c = [color[0], color[1], color[2], u8::MAX];
+            config
+                .run::<_, BitRenderMode>(shape)
+                .unwrap()
+                .map(|p| if *p { c } else { [0u8; 4] })
+        }
+        Mode2D::Sdf => config
+            .run::<_, SdfRenderMode>(shape)
+            .unwrap()
+            .map(|&[r, g, b]| [r, g, b, u8::MAX]),
+        Mode2D::ExactSdf => config
+            .run::<_, SdfPixelRenderMode>(shape)
+            .unwrap()
+            .map(|&[r, g, b]| [r, g, b, u8::MAX]),
+        Mode2D::Debug => config
+            .run::<_, DebugRenderMode>(shape)
+            .unwrap()
+            .map(|p| p.as_debug_color()),
+    };
+    let (data, _) = out.take();
+    data
+}
+
+/// Renders the XY plane of all shapes
+///
+/// The layers from [`layers_2d`] are drawn on top of each other.
+pub fn render_2d<F: Function + MathFunction + RenderHints>(
+    ctx: &ScriptContext,
+    mode: Mode2D,
+    width: u32,
+    height: u32,
+) -> Result<Vec<[u8; 4]>, fidget::Error> {
+    let image_size = ImageSize::new(width, height);
+    let view = ctx.view.view_2d();
+    let mut image = vec![[0u8; 4]; (width * height) as usize];
+    for (tree, color) in layers_2d(ctx, mode)? {
+        let layer = render_layer_2d::<F>(mode, tree, view, image_size, color);
+        for (out, p) in image.iter_mut().zip(layer) {
+            if p[3] != 0 {
+                *out = p;
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Lights as `(position, intensity)`, matching the viewer
+const LIGHTS: [([f32; 3], f32); 3] = [
+    ([5.0, -5.0, 10.0], 0.5),
+    ([-5.0, 0.0, 10.0], 0.15),
+    ([0.0, -5.0, 10.0], 0.15),
+];
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let norm = v.iter().map(|c| c * c).sum::<f32>().sqrt();
+    v.map(|c| c / norm)
+}
+
+/// Brightness of a surface point with a normalized normal, from 0 to 1
+///
+/// `pos` is in the `[-1, 1]` cube, with +Z towards the viewer.
+fn shade(pos: [f32; 3], normal: [f32; 3]) -> f32 {
+    let mut accum = 0.2;
+    for (light, intensity) in LIGHTS {
+        let dir = normalize(std::array::from_fn(|i| light[i] - pos[i]));
+        let dot = (0..3).map(|i| dir[i] * normal[i]).sum::<f32>();
+        accum += dot.max(0.0) * intensity;
+    }
+    accum.clamp(0.0, 1.0)
+}
+
+/// Renders a single layer from [`layers_3d`] to a geometry buffer
+pub fn render_layer_3d<F: Function + MathFunction + RenderHints>(
+    tree: Tree,
+    view: View3,
+    image_size: VoxelSize,
+) -> Vec<GeometryPixel> {
+    let config = VoxelRenderConfig {
+        image_size,
+        tile_sizes: F::tile_sizes_3d(),
+        view,
+        ..Default::default()
+    };
+    // `None` means the render was cancelled, which we never do
+    let (data, _) = config.run(Shape::<F>::from(tree)).unwrap().take();
+    data
+}
+
+/// Renders all shapes in 3D, showing the nearest surface at every pixel
+///
+/// The depth resolution is the larger of `width` and `height`.
+pub fn render_3d<F: Function + MathFunction + RenderHints>(
+    ctx: &ScriptContext,
+    mode: Mode3D,
+    width: u32,
+    height: u32,
+) -> Result<Vec<[u8; 4]>, fidget::Error> {
+    let depth = width.max(height);
+    let image_size = VoxelSize::new(width, height, depth);
+    let view = ctx.view.view_3d();
+
+    // Merge the geometry buffers, remembering the color of the nearest shape
+    let mut geometry: Vec<Option<(GeometryPixel, [u8; 3])>> = vec![None; (width * height) as usize];
+    for (tree, color) in layers_3d(ctx)? {
+        let data = render_layer_3d::<F>(tree, view, image_size);
+        for (out, p) in geometry.iter_mut().zip(data) {
+            if p.depth > out.as_ref().map_or(0, |(q, _)| q.depth) {
+                *out = Some((p, color));
+            }
+        }
+    }
+
+    let image = geometry
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let Some((p, color)) = p else {
+                return [0u8; 4];
+            };
+            let z = p.depth as f32 / depth as f32;
+            let normal = normalize(p.normal);
+            let rgb = match mode {
+                Mode3D::Heightmap => [z; 3],
+                Mode3D::Normals => normal.map(f32::abs),
+                Mode3D::Shaded => {
+                    let (row, col) = (i as u32 / width, i as u32 % width);
+                    let u = (col as f32 + 0.5) / width as f32;
+                    let v = (row as f32 + 0.5) / height as f32;
+                    let pos = [(u - 0.5) * 2.0, (v - 0.5) * 2.0, (z - 0.5) * 2.0];
+                    let s = shade(pos, normal);
+                    color.map(|c| c as f32 / 255.0 * s)
+                }
+            };
+            let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            [r, g, b, u8::MAX]
+        })
+        .collect();
+    Ok(image)
+}
@@ -0,0 +1,502 @@
+//! Compatibility with fidget's Rhai models
+//!
+//! There are two parts:
+//!
+//! - a compatibility prelude, enabled with
+//!   [`EngineSettings::with_rhai_compat`](crate::EngineSettings::with_rhai_compat),
+//!   which provides `draw_rgb` and replaces a few builtins with their
+//!   Rhai-style names and argument orders:
+//!
+//!   | Rhai                           | Koto equivalent                 |
+//!   |--------------------------------|---------------------------------|
+//!   | `draw_rgb(shape, r, g, b)`     | `draw shape, r, g, b`           |
+//!   | `axes()` (map with `x, y, z`)  | `axes()` (tuple)                |
+//!   | `circle(cx, cy, r)`            | `circle r, cx, cy`              |
+//!   | `sphere(cx, cy, cz, r)`        | `sphere r, cx, cy, cz`          |
+//!   | `move_xy(shape, dx, dy)`       | `move shape, dx, dy, 0`         |
+//!   | `move_xyz(shape, dx, dy, dz)`  | `move shape, dx, dy, dz`        |
+//!   | `scale_xy(shape, sx, sy)`      | `scale shape, sx, sy`           |
+//!   | `scale_xyz(shape, sx, sy, sz)` | `scale shape, sx, sy, sz`       |
+//!
+//! - [`translate`], a best-effort source translator from Rhai to Koto for the
+//!   subset of Rhai used by models: `let`/`const` bindings, functions,
+//!   closures, `if`/`else`, `for`, `while` and `loop`, object maps, arrays
+//!   and comments.  Translated models are meant to run with the compatibility
+//!   prelude.
+//!
+//! Rhai hoists functions, so they can be called before they are defined;
+//! Koto doesn't, so translated functions may need to be moved up.
+
+use crate::args::{Param, parse_args};
+use crate::{KCircle, KMove, KScale, KSphere, KTree};
+use fidget::context::Tree;
+use koto::prelude::*;
+
+/// Registers the Rhai-style builtins, replacing the Koto ones of the same name
+pub(crate) fn add_rhai_fns(prelude: &KMap) {
+    prelude.add_fn("axes", |_| {
+        let (x, y, z) = Tree::axes();
+        let axes = KMap::new();
+        axes.insert("x", KTree::from(x));
+        axes.insert("y", KTree::from(y));
+        axes.insert("z", KTree::from(z));
+        Ok(axes.into())
+    });
+
+    prelude.add_fn("circle", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[Param::number("cx"), Param::number("cy"), Param::number("r")]],
+        )?;
+        let circle = KCircle::new(args.number(2), args.number(0), args.number(1));
+        Ok(KValue::Object(circle))
+    });
+
+    prelude.add_fn("sphere", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::number("cx"),
+                Param::number("cy"),
+                Param::number("cz"),
+                Param::number("r"),
+            ]],
+        )?;
+        let sphere = KSphere::new(
+            args.number(3),
+            args.number(0),
+            args.number(1),
+            args.number(2),
+        );
+        Ok(KValue::Object(sphere))
+    });
+
+    prelude.add_fn("move_xy", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::shape("shape"),
+                Param::number("dx"),
+                Param::number("dy"),
+            ]],
+        )?;
+        let shape = KMove::new(args.tree(0), args.number(1), args.number(2), 0.0);
+        Ok(KValue::Object(shape))
+    });
+
+    prelude.add_fn("move_xyz", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::shape("shape"),
+                Param::number("dx"),
+                Param::number("dy"),
+                Param::number("dz"),
+            ]],
+        )?;
+        let shape = KMove::new(args.tree(0), args.number(1), args.number(2), args.number(3));
+        Ok(KValue::Object(shape))
+    });
+
+    prelude.add_fn("scale_xy", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::shape("shape"),
+                Param::number("sx"),
+                Param::number("sy"),
+            ]],
+        )?;
+        let shape = KScale::new(args.tree(0), args.number(1), args.number(2), 1.0);
+        Ok(KValue::Object(shape))
+    });
+
+    prelude.add_fn("scale_xyz", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[
+                Param::shape("shape"),
+                Param::number("sx"),
+                Param::number("sy"),
+                Param::number("sz"),
+            ]],
+        )?;
+        let shape = KScale::new(args.tree(0), args.number(1), args.number(2), args.number(3));
+        Ok(KValue::Object(shape))
+    });
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error for Rhai code which can't be translated
+#[derive(Debug, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct TranslateError {
+    /// Line in the Rhai source, starting at 1
+    pub line: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Ident,
+    Number,
+    /// String literal, already in Koto syntax
+    Str,
+    Punct,
+    Comment,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+    /// Whether the token is preceded by whitespace
+    space_before: bool,
+    /// Whether the token is the first on its line
+    line_start: bool,
+}
+
+const PUNCTS: [&str; 20] = [
+    "..=", "#{", "&&", "||", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "%=", "..", "=>",
+    "**", "::", "->", "<<", ">>",
+];
+
+/// Converts the body of a Rhai string to Koto, where `{` starts interpolation
+fn escape_string(body: &str, interpolated: bool) -> String {
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            '$' if interpolated && chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '{' => out.push_str("\\{"),
+            '"' if interpolated => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, TranslateError> {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    let mut line = 1;
+    let mut space_before = false;
+    let mut line_start = true;
+    let error = |line, message: &str| TranslateError {
+        line,
+        message: message.to_owned(),
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        let rest = chars[i..].iter().take(3).collect::<String>();
+        let (kind, text) = if c == '\n' {
+            line += 1;
+            i += 1;
+            space_before = true;
+            line_start = true;
+            continue;
+        } else if c.is_whitespace() {
+            i += 1;
+            space_before = true;
+            continue;
+        } else if rest.starts_with("//") {
+            let end = chars[i..]
+                .iter()
+                .position(|c| *c == '\n')
+                .map_or(chars.len(), |p| i + p);
+            let text = chars[i + 2..end].iter().collect::<String>();
+            i = end;
+            (TokenKind::Comment, text.trim().to_owned())
+        } else if rest.starts_with("/*") {
+            let mut j = i + 2;
+            while j + 1 < chars.len() && !(chars[j] == '*' && chars[j + 1] == '/') {
+                j += 1;
+            }
+            if j + 1 >= chars.len() {
+                return Err(error(start_line, "unterminated comment"));
+            }
+            let text = chars[i + 2..j].iter().collect::<String>();
+            line += text.matches('\n').count();
+            i = j + 2;
+            (TokenKind::Comment, text)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = chars[i..]
+                .iter()
+                .position(|c| !(c.is_ascii_alphanumeric() || *c == '_'))
+                .map_or(chars.len(), |p| i + p);
+            let text = chars[i..end].iter().collect::<String>();
+            i = end;
+            (TokenKind::Ident, text)
+        } else if c.is_ascii_digit() {
+            let mut j = i;
+            while j < chars.len()
+                && (chars[j].is_ascii_alphanumeric()
+                    || chars[j] == '_'
+                    // Not `1..2` or `1.abs()`
+                    || (chars[j] == '.' && chars.get(j + 1).is_some_and(|c| c.is_ascii_digit()))
+                    || ((chars[j] == '-' || chars[j] == '+')
+                        && matches!(chars[j - 1], 'e' | 'E')
+                        && !chars[i..j].iter().any(|c| *c == 'x')))
+            {
+                j += 1;
+            }
+            let text = chars[i..j].iter().filter(|c| **c != '_').collect();
+            i = j;
+            (TokenKind::Number, text)
+        } else if c == '"' || c == '`' || c == '\'' {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c {
+                if chars[j] == '\\' {
+                    j += 1;
+                }
+                j += 1;
+            }
+            if j >= chars.len() {
+                return Err(error(start_line, "unterminated string"));
+            }
+            let body = chars[i + 1..j].iter().collect::<String>();
+            line += body.matches('\n').count();
+            i = j + 1;
+            let body = match c {
+                '`' => escape_string(&body, true),
+                '\'' if body == "\"" => "\\\"".to_owned(),
+                _ => escape_string(&body, false),
+            };
+            (TokenKind::Str, format!("\"{body}\""))
+        } else if let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+            i += p.len();
+            (TokenKind::Punct, p.to_string())
+        } else {
+            i += 1;
+            (TokenKind::Punct, c.to_string())
+        };
+        tokens.push(Token {
+            kind,
+            text,
+            line: start_line,
+            space_before,
+            line_start,
+        });
+        space_before = false;
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+/// What a `{` opened
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Brace {
+    /// Block with a header (`if`, `fn`, ...), translated to an indented block
+    Block,
+    /// Bare block, whose contents stay at the same indentation
+    Bare,
+    /// Object map
+    Map,
+}
+
+/// Builds the Koto source line by line
+struct Writer {
+    lines: Vec<String>,
+    current: String,
+    comment: Option<String>,
+    indent: usize,
+    braces: Vec<Brace>,
+    /// Nesting depth of parentheses and brackets
+    depth: usize,
+    /// Whether the next token needs a space before it, e.g. after `and`
+    space_next: bool,
+}
+
+impl Writer {
+    fn push(&mut self, text: &str, space: bool) {
+        let space = space || std::mem::take(&mut self.space_next);
+        if space && !self.current.is_empty() && !self.current.ends_with(' ') {
+            self.current.push(' ');
+        }
+        self.current.push_str(text);
+    }
+
+    fn flush(&mut self) {
+        let mut line = self.current.trim().to_owned();
+        if let Some(comment) = self.comment.take() {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&comment);
+        }
+        if !line.is_empty() {
+            self.lines
+                .push(format!("{}{line}", "  ".repeat(self.indent)));
+        }
+        self.current.clear();
+    }
+
+    fn last_is_operand(&self) -> bool {
+        self.current
+            .trim_end()
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | ')' | ']' | '"' | '}'))
+    }
+}
+
+/// Translates Rhai source code to Koto
+///
+/// Unsupported constructs (`switch`, `::` paths, `**`, closures with block
+/// bodies inside expressions, ...) are reported as errors.
+pub fn translate(src: &str) -> Result<String, TranslateError> {
+    let tokens = tokenize(src)?;
+    let mut w = Writer {
+        lines: vec![],
+        current: String::new(),
+        comment: None,
+        indent: 0,
+        braces: vec![],
+        depth: 0,
+        space_next: false,
+    };
+    let unsupported = |t: &Token, what: &str| TranslateError {
+        line: t.line,
+        message: format!("{what} is not supported"),
+    };
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let t = &tokens[i];
+        let next = tokens.get(i + 1);
+        i += 1;
+        match (t.kind.clone(), t.text.as_str()) {
+            (TokenKind::Comment, text) => {
+                let comment = text
+                    .lines()
+                    .map(|l| format!("# {}", l.trim()).trim_end().to_owned())
+                    .collect::<Vec<_>>();
+                if t.line_start || comment.len() > 1 {
+                    let current = std::mem::take(&mut w.current);
+                    let pending = w.comment.take();
+                    for c in comment {
+                        w.current = c;
+                        w.flush();
+                    }
+                    w.current = current;
+                    w.comment = pending;
+                } else {
+                    w.comment = Some(comment.join(" "));
+                }
+            }
+            (TokenKind::Ident, "let" | "const") if w.current.trim().is_empty() => (),
+            (TokenKind::Ident, "fn") => {
+                let name = next
+                    .filter(|n| n.kind == TokenKind::Ident)
+                    .ok_or_else(|| unsupported(t, "anonymous `fn`"))?;
+                if tokens.get(i + 1).map(|t| t.text.as_str()) != Some("(") {
+                    return Err(unsupported(t, "`fn` without parameters"));
+                }
+                let mut params = String::new();
+                i += 2;
+                while let Some(p) = tokens.get(i) {
+                    i += 1;
+                    match p.text.as_str() {
+                        ")" => break,
+                        "," => params.push_str(", "),
+                        text => params.push_str(text),
+                    }
+                }
+                w.flush();
+                w.push(&format!("{} = |{params}|", name.text), false);
+            }
+            (TokenKind::Ident, "switch") => return Err(unsupported(t, "`switch`")),
+            (TokenKind::Ident, "this") => w.push("self", t.space_before),
+            (TokenKind::Punct, "::") => return Err(unsupported(t, "`::` path")),
+            (TokenKind::Punct, "**") => return Err(unsupported(t, "`**`")),
+            (TokenKind::Punct, "=>") => return Err(unsupported(t, "`=>`")),
+            (TokenKind::Punct, "#{") => {
+                w.braces.push(Brace::Map);
+                w.push("{", t.space_before);
+            }
+            (TokenKind::Punct, "{") => {
+                if w.depth > 0 {
+                    return Err(unsupported(t, "a block inside an expression"));
+                }
+                if w.current.trim().is_empty() {
+                    w.braces.push(Brace::Bare);
+                } else {
+                    w.flush();
+                    w.indent += 1;
+                    w.braces.push(Brace::Block);
+                }
+            }
+            (TokenKind::Punct, "}") => match w.braces.pop() {
+                Some(Brace::Map) => w.push("}", t.space_before),
+                Some(Brace::Block) => {
+                    w.flush();
+                    w.indent -= 1;
+                }
+                Some(Brace::Bare) => w.flush(),
+                None => {
+                    return Err(TranslateError {
+                        line: t.line,
+                        message: "unbalanced `}`".to_owned(),
+                    });
+                }
+            },
+            (TokenKind::Punct, ";") if w.depth == 0 => w.flush(),
+            (TokenKind::Punct, "(" | "[") => {
+                // `()` is Rhai's unit value, unless it's a call without
+                // arguments
+                if t.text == "(" && next.is_some_and(|n| n.text == ")") && !w.last_is_operand() {
+                    i += 1;
+                    w.push("null", t.space_before);
+                } else {
+                    w.depth += 1;
+                    w.push(&t.text, t.space_before);
+                }
+            }
+            (TokenKind::Punct, ")" | "]") => {
+                w.depth = w.depth.saturating_sub(1);
+                w.push(&t.text, t.space_before);
+            }
+            (TokenKind::Punct, "&&") => {
+                w.push("and", true);
+                w.space_next = true;
+            }
+            // `||` is also a closure without parameters
+            (TokenKind::Punct, "||") if w.last_is_operand() => {
+                w.push("or", true);
+                w.space_next = true;
+            }
+            (TokenKind::Punct, "!") => {
+                w.push("not", t.space_before);
+                w.space_next = true;
+            }
+            (_, text) => w.push(text, t.space_before),
+        }
+    }
+    if !w.braces.is_empty() {
+        return Err(TranslateError {
+            line: tokens.last().map_or(1, |t| t.line),
+            message: "unbalanced `{`".to_owned(),
+        });
+    }
+    w.flush();
+
+    let mut out = w.lines.join("\n");
+    out.push('\n');
+    Ok(out)
+}
@@ -0,0 +1,324 @@
+//! Code generation of GLSL and WGSL functions from [`Tree`]s
+//!
+//! The generated function takes the sample position and returns the value of
+//! the tree:
+//!
+//! ```glsl
+//! float sdf(vec3 p) {
+//!     float _0 = p.x * p.x;
+//!     float _1 = p.y * p.y;
+//!     float _2 = _0 + _1;
+//!     float _3 = sqrt(_2);
+//!     return _3 - 1.0;
+//! }
+//! ```
+//!
+//! Every operation is bound to a local, so shared subtrees are evaluated once
+//! and the output grows with the number of unique nodes.  Variables other
+//! than `x`, `y` and `z` become extra `float` parameters, in the order
+//! returned by [`inspect::vars`] and named as described in [`function`].
+//!
+//! The output matches fidget's evaluators except for corner cases: `compare`
+//! returns 0 rather than NaN if either side is NaN, and `min`/`max` follow
+//! the shading language's NaN handling.
+
+use crate::NamedVar;
+use crate::inspect::{self, children, post_order};
+use fidget::{
+    context::{BinaryOpcode, Context, Node, Op, Tree, UnaryOpcode},
+    var::Var,
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Shading language
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Language {
+    /// GLSL, e.g. for Shadertoy or OpenGL
+    Glsl,
+    /// WGSL, for WebGPU
+    Wgsl,
+}
+
+/// Generates a GLSL function `float name(vec3 p, ...)`
+pub fn glsl(tree: &Tree, name: &str, vars: &[NamedVar]) -> String {
+    function(tree, name, vars, Language::Glsl)
+}
+
+/// Generates a WGSL function `fn name(p: vec3<f32>, ...) -> f32`
+pub fn wgsl(tree: &Tree, name: &str, vars: &[NamedVar]) -> String {
+    function(tree, name, vars, Language::Wgsl)
+}
+
+/// Keywords, type names and builtin functions of either language
+///
+/// This isn't every reserved word, only the ones which are likely to be used
+/// as variable names.
+const RESERVED: &str = "\
+    alias attribute bool break buffer case centroid const continue continuing \
+    default discard do double else enable enum false flat fn for highp if in \
+    inout int invariant layout let loop lowp mediump out override patch \
+    precise precision return sample shared smooth struct switch true uniform \
+    var varying void while \
+    array atomic bvec2 bvec3 bvec4 f16 f32 float i32 ivec2 ivec3 ivec4 mat2 \
+    mat3 mat4 ptr sampler u32 uint uvec2 uvec3 uvec4 vec2 vec3 vec4 \
+    abs acos asin atan atan2 bitcast ceil clamp cos cosh cross degrees \
+    distance dot exp exp2 floor fma fract inversesqrt length log log2 max min \
+    mix mod normalize pow radians reflect round select sign sin sinh \
+    smoothstep sqrt step tan tanh trunc uintBitsToFloat";
+
+/// Checks whether a variable name can be used as a parameter name
+///
+/// Leading underscores are reserved for locals, and `p` for the position.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.contains("__")
+        && !name.starts_with("gl_")
+        && name != "p"
+        && !RESERVED.split_whitespace().any(|w| w == name)
+}
+
+fn literal(value: f64, lang: Language) -> String {
+    let v = value as f32;
+    if v.is_finite() {
+        let s = format!("{v:?}");
+        if v.is_sign_negative() {
+            format!("({s})")
+        } else {
+            s
+        }
+    } else {
+        let bits = v.to_bits();
+        match lang {
+            Language::Glsl => format!("uintBitsToFloat({bits:#x}u)"),
+            Language::Wgsl => format!("bitcast<f32>({bits:#x}u)"),
+        }
+    }
+}
+
+fn unary(lang: Language, opcode: UnaryOpcode, a: &str) -> String {
+    let f = |name: &str| format!("{name}({a})");
+    match opcode {
+        UnaryOpcode::Neg => format!("-{a}"),
+        UnaryOpcode::Abs => f("abs"),
+        UnaryOpcode::Recip => format!("1.0 / {a}"),
+        UnaryOpcode::Sqrt => f("sqrt"),
+        UnaryOpcode::Square => format!("{a} * {a}"),
+        UnaryOpcode::Floor => f("floor"),
+        UnaryOpcode::Ceil => f("ceil"),
+        // Half away from zero, like f64::round
+        UnaryOpcode::Round => format!("sign({a}) * floor(abs({a}) + 0.5)"),
+        UnaryOpcode::Sin => f("sin"),
+        UnaryOpcode::Cos => f("cos"),
+        UnaryOpcode::Tan => f("tan"),
+        UnaryOpcode::Asin => f("asin"),
+        UnaryOpcode::Acos => f("acos"),
+        UnaryOpcode::Atan => f("atan"),
+        UnaryOpcode::Exp => f("exp"),
+        UnaryOpcode::Ln => f("log"),
+        UnaryOpcode::Not => match lang {
+            Language::Glsl => format!("float({a} == 0.0)"),
+            Language::Wgsl => format!("select(0.0, 1.0, {a} == 0.0)"),
+        },
+    }
+}
+
+fn binary(lang: Language, opcode: BinaryOpcode, a: &str, b: &str) -> String {
+    // `and` picks `a` if it is zero, `or` picks `a` if it is non-zero
+    let pick = |cond: &str| match lang {
+        Language::Glsl => format!("{a} {cond} 0.0 ? {a} : {b}"),
+        Language::Wgsl => format!("select({b}, {a}, {a} {cond} 0.0)"),
+    };
+    let float = match lang {
+        Language::Glsl => "float",
+        Language::Wgsl => "f32",
+    };
+    match opcode {
+        BinaryOpcode::Add => format!("{a} + {b}"),
+        BinaryOpcode::Sub => format!("{a} - {b}"),
+        BinaryOpcode::Mul => format!("{a} * {b}"),
+        BinaryOpcode::Div => format!("{a} / {b}"),
+        BinaryOpcode::Atan => match lang {
+            Language::Glsl => format!("atan({a}, {b})"),
+            Language::Wgsl => format!("atan2({a}, {b})"),
+        },
+        BinaryOpcode::Min => format!("min({a}, {b})"),
+        BinaryOpcode::Max => format!("max({a}, {b})"),
+        BinaryOpcode::Compare => format!("{float}({a} > {b}) - {float}({a} < {b})"),
+        // Least non-negative remainder, like f64::rem_euclid
+        BinaryOpcode::Mod => format!("{a} - abs({b}) * floor({a} / abs({b}))"),
+        BinaryOpcode::And => pick("=="),
+        BinaryOpcode::Or => pick("!="),
+    }
+}
+
+/// Generates a function named `name` in the given language
+///
+/// Parameters are named after `vars`, see [`inspect::var_name`].  Names which
+/// aren't valid identifiers, or which clash with a keyword, a builtin, `name`
+/// or an earlier parameter, are replaced by `var0`, `var1`, etc.
+pub fn function(tree: &Tree, name: &str, vars: &[NamedVar], lang: Language) -> String {
+    let mut ctx = Context::new();
+    let root = ctx.import(tree);
+
+    let mut params: Vec<String> = vec![];
+    let mut var_names: HashMap<Var, String> = HashMap::new();
+    for var in inspect::vars(tree) {
+        let expr = match var {
+            Var::X => "p.x".to_owned(),
+            Var::Y => "p.y".to_owned(),
+            Var::Z => "p.z".to_owned(),
+            v => {
+                let var_name = inspect::var_name(&v, vars);
+                let taken = |n: &str| n == name || params.iter().any(|p| p == n);
+                let param = if is_identifier(&var_name) && !taken(&var_name) {
+                    var_name
+                } else {
+                    (0..)
+                        .map(|i| format!("var{i}"))
+                        .find(|n| !taken(n))
+                        .unwrap()
+                };
+                params.push(param.clone());
+                param
+            }
+        };
+        var_names.insert(var, expr);
+    }
+
+    let mut out = String::new();
+    match lang {
+        Language::Glsl => {
+            write!(out, "float {name}(vec3 p").unwrap();
+            for param in &params {
+                write!(out, ", float {param}").unwrap();
+            }
+            out.push_str(") {\n");
+        }
+        Language::Wgsl => {
+            write!(out, "fn {name}(p: vec3<f32>").unwrap();
+            for param in &params {
+                write!(out, ", {param}: f32").unwrap();
+            }
+            out.push_str(") -> f32 {\n");
+        }
+    }
+
+    // Leaves are written in place; every operation gets its own local
+    let mut exprs: HashMap<Node, String> = HashMap::new();
+    let mut locals = 0;
+    for node in post_order(&ctx, root) {
+        let op = ctx.get_op(node).unwrap();
+        let arg = |c: &Node| exprs[c].as_str();
+        let expr = match op {
+            Op::Input(var) => var_names[var].clone(),
+            Op::Const(c) => literal(c.0, lang),
+            Op::Unary(opcode, a) => unary(lang, *opcode, arg(a)),
+            Op::Binary(opcode, a, b) => binary(lang, *opcode, arg(a), arg(b)),
+        };
+        if children(op).is_empty() {
+            exprs.insert(node, expr);
+        } else if node == root {
+            writeln!(out, "    return {expr};").unwrap();
+        } else {
+            let local = format!("_{locals}");
+            locals += 1;
+            match lang {
+                Language::Glsl => writeln!(out, "    float {local} = {expr};").unwrap(),
+                Language::Wgsl => writeln!(out, "    let {local} = {expr};").unwrap(),
+            }
+            exprs.insert(node, local);
+        }
+    }
+    if let Some(expr) = exprs.get(&root) {
+        writeln!(out, "    return {expr};").unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trees() -> Vec<(Tree, Vec<NamedVar>)> {
+        let named = |name: &str| NamedVar {
+            name: name.to_owned(),
+            var: Var::new(),
+            default: 0.0,
+        };
+        let (x, y, z) = Tree::axes();
+        let circle = (x.square() + y.square()).sqrt() - Tree::constant(1.0);
+        let ops = x.atan2(y.clone()).compare(z.round()).modulo(y.clone())
+            + x.not().and(y.clone()).or(z.recip().ln());
+
+        // Clashes with keywords, builtins, the function and each other
+        let vars = ["in", "sin", "sdf", "t", "t", "var0", "my var"].map(named);
+        let sum = vars.iter().fold(-x, |acc, v| acc + Tree::from(v.var));
+        vec![(circle, vec![]), (ops, vec![]), (sum, vars.to_vec())]
+    }
+
+    #[test]
+    fn params() {
+        let (tree, vars) = trees().pop().unwrap();
+        let out = wgsl(&tree, "sdf", &vars);
+        let params = out
+            .lines()
+            .next()
+            .and_then(|h| h.strip_prefix("fn sdf(p: vec3<f32>, "))
+            .and_then(|h| h.strip_suffix(") -> f32 {"))
+            .unwrap()
+            .split(", ")
+            .map(|p| p.strip_suffix(": f32").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(params.len(), vars.len());
+        assert_eq!(params.iter().filter(|p| **p == "t").count(), 1);
+        for (i, p) in params.iter().enumerate() {
+            assert!(is_identifier(p) && *p != "sdf", "bad parameter {p}");
+            assert!(!params[..i].contains(p), "duplicate parameter {p}");
+        }
+    }
+
+    #[test]
+    fn glsl_compiles() {
+        for (tree, vars) in trees() {
+            let args = std::iter::once("p")
+                .chain(vars.iter().map(|_| "1.0"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let src = format!(
+                "#version 450\n{}\n\
+                 layout(location = 0) in vec3 p;\n\
+                 layout(location = 0) out vec4 color;\n\
+                 void main() {{ color = vec4(sdf({args})); }}\n",
+                glsl(&tree, "sdf", &vars),
+            );
+            let options = naga::front::glsl::Options::from(naga::ShaderStage::Fragment);
+            let module = naga::front::glsl::Frontend::default()
+                .parse(&options, &src)
+                .unwrap_or_else(|e| panic!("{e:?}\n{src}"));
+            validate(&module, &src);
+        }
+    }
+
+    #[test]
+    fn wgsl_compiles() {
+        for (tree, vars) in trees() {
+            let src = wgsl(&tree, "sdf", &vars);
+            let module = naga::front::wgsl::parse_str(&src)
+                .unwrap_or_else(|e| panic!("{}\n{src}", e.emit_to_string(&src)));
+            validate(&module, &src);
+        }
+    }
+
+    fn validate(module: &naga::Module, src: &str) {
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(module)
+        .unwrap_or_else(|e| panic!("{e:?}\n{src}"));
+    }
+}
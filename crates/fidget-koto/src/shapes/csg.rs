@@ -6,10 +6,15 @@ use koto::{derive::*, prelude::*, runtime};
 use std::fmt;
 
 use crate::KTree;
+use crate::args::{Param, parse_args};
+use crate::utils::maybe_tree;
 
 /// KotoObject wrapper for fidget Difference
+///
+/// The shape and cutout are also kept as the objects they were built from, so
+/// that `shape` and `cutout` return e.g. spheres rather than trees.
 #[derive(Clone, KotoCopy, KotoType)]
-pub struct KDifference(Difference);
+pub struct KDifference(Difference, KObject, KObject);
 
 impl KotoObject for KDifference {
     fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
@@ -20,7 +25,9 @@ impl KotoObject for KDifference {
 
 impl From<Difference> for KDifference {
     fn from(tree: Difference) -> Self {
-        Self(tree)
+        let shape = KObject::from(KTree::from(tree.shape.clone()));
+        let cutout = KObject::from(KTree::from(tree.cutout.clone()));
+        Self(tree, shape, cutout)
     }
 }
 
@@ -40,13 +47,21 @@ impl fmt::Display for KDifference {
 impl KDifference {
     /// Create KotoObject representing fidget::shapes::Difference
     pub fn new(a: Tree, b: Tree) -> KObject {
-        KObject::from(KDifference(
-            Difference {
-                shape: a,
-                cutout: b,
-            }
-            .into(),
-        ))
+        KObject::from(Self::from(Difference {
+            shape: a,
+            cutout: b,
+        }))
+    }
+
+    /// Create KotoObject cutting one shape object out of another
+    ///
+    /// Returns `None` if an object is not a shape.
+    pub fn from_inputs(shape: KObject, cutout: KObject) -> Option<KObject> {
+        let inner = Difference {
+            shape: maybe_tree(&shape)?,
+            cutout: maybe_tree(&cutout)?,
+        };
+        Some(KObject::from(Self(inner, shape, cutout)))
     }
 
     /// Access the inner fidget Difference struct
@@ -61,11 +76,88 @@ impl KDifference {
             self.inner(),
         )))))
     }
+
+    /// The shape which is cut into, as the object passed in
+    #[koto_get]
+    fn shape(&self) -> runtime::Result<KValue> {
+        Ok(self.1.clone().into())
+    }
+
+    /// Copy with a different shape being cut into
+    #[koto_method]
+    fn with_shape(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        let cutout = ctx.instance()?.2.clone();
+        inner.shape = args.tree(0);
+        Ok(Self(inner, args.object(0), cutout).into())
+    }
+
+    /// The shape which is cut out, as the object passed in
+    #[koto_get]
+    fn cutout(&self) -> runtime::Result<KValue> {
+        Ok(self.2.clone().into())
+    }
+
+    /// Copy with a different cutout
+    #[koto_method]
+    fn with_cutout(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("cutout")]])?;
+        let mut inner = ctx.instance()?.inner();
+        let shape = ctx.instance()?.1.clone();
+        inner.cutout = args.tree(0);
+        Ok(Self(inner, shape, args.object(0)).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
 
 /// KotoObject wrapper for fidget Intersection
+///
+/// The inputs are also kept as the objects they were built from, so that
+/// `inputs` returns e.g. spheres rather than trees.
 #[derive(Clone, KotoCopy, KotoType)]
-pub struct KIntersection(Intersection);
+pub struct KIntersection(Intersection, Vec<KObject>);
 
 impl KotoObject for KIntersection {
     fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
@@ -76,7 +168,8 @@ impl KotoObject for KIntersection {
 
 impl From<Intersection> for KIntersection {
     fn from(tree: Intersection) -> Self {
-        Self(tree)
+        let inputs = tree_objects(&tree.input);
+        Self(tree, inputs)
     }
 }
 
@@ -96,7 +189,15 @@ impl fmt::Display for KIntersection {
 impl KIntersection {
     /// Create KotoObject representing fidget::shapes::Intersection
     pub fn new(a: Tree, b: Tree) -> KObject {
-        KObject::from(Self(Intersection { input: vec![a, b] }.into()))
+        KObject::from(Self::from(Intersection { input: vec![a, b] }))
+    }
+
+    /// Create KotoObject combining shape objects, which `inputs` returns
+    ///
+    /// Returns `None` if an object is not a shape.
+    pub fn from_inputs(inputs: Vec<KObject>) -> Option<KObject> {
+        let input = input_trees(&inputs)?;
+        Some(KObject::from(Self(Intersection { input }, inputs)))
     }
 
     /// Access the inner fidget Intersection struct
@@ -111,11 +212,99 @@ impl KIntersection {
             self.inner(),
         )))))
     }
+
+    /// The shapes which are combined, as a list of the objects passed in
+    #[koto_get]
+    fn inputs(&self) -> runtime::Result<KValue> {
+        let inputs = self
+            .1
+            .iter()
+            .cloned()
+            .map(KValue::Object)
+            .collect::<Vec<_>>();
+        Ok(KValue::List(KList::from_slice(&inputs)))
+    }
+
+    /// Copy of the intersection with different inputs
+    #[koto_method]
+    fn with_inputs(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let inputs = match ctx.args {
+            [KValue::List(list)] if !list.data().is_empty() => list
+                .data()
+                .iter()
+                .map(|value| match value {
+                    KValue::Object(obj) => Some(obj.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        match inputs.and_then(Self::from_inputs) {
+            Some(obj) => Ok(obj.into()),
+            None => unexpected_args("|inputs: non-empty List of shapes|", ctx.args),
+        }
+    }
+
+    /// Copy of the intersection with another shape added to its inputs
+    #[koto_method]
+    fn add(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        let mut inputs = ctx.instance()?.1.clone();
+        inner.input.push(args.tree(0));
+        inputs.push(args.object(0));
+        Ok(Self(inner, inputs).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
 
 /// KotoObject wrapper for fidget Inverse
+///
+/// The shape is also kept as the object it was built from, so that `shape`
+/// returns e.g. a sphere rather than a tree.
 #[derive(Clone, KotoCopy, KotoType)]
-pub struct KInverse(Inverse);
+pub struct KInverse(Inverse, KObject);
 
 impl KotoObject for KInverse {
     fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
@@ -126,7 +315,8 @@ impl KotoObject for KInverse {
 
 impl From<Inverse> for KInverse {
     fn from(tree: Inverse) -> Self {
-        Self(tree)
+        let shape = KObject::from(KTree::from(tree.shape.clone()));
+        Self(tree, shape)
     }
 }
 
@@ -146,7 +336,17 @@ impl fmt::Display for KInverse {
 impl KInverse {
     /// Create KotoObject representing fidget::shapes::Inverse
     pub fn new(shape: Tree) -> KObject {
-        KObject::from(Self(Inverse { shape }.into()))
+        KObject::from(Self::from(Inverse { shape }))
+    }
+
+    /// Create KotoObject inverting a shape object
+    ///
+    /// Returns `None` if the object is not a shape.
+    pub fn from_input(shape: KObject) -> Option<KObject> {
+        let inner = Inverse {
+            shape: maybe_tree(&shape)?,
+        };
+        Some(KObject::from(Self(inner, shape)))
     }
 
     /// Access the inner fidget Inverse struct
@@ -161,11 +361,71 @@ impl KInverse {
             self.inner(),
         )))))
     }
+
+    /// The shape being inverted, as the object passed in
+    #[koto_get]
+    fn shape(&self) -> runtime::Result<KValue> {
+        Ok(self.1.clone().into())
+    }
+
+    /// Copy with a different shape being inverted
+    #[koto_method]
+    fn with_shape(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        inner.shape = args.tree(0);
+        Ok(Self(inner, args.object(0)).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
 
 /// KotoObject wrapper for fidget Union
+///
+/// The inputs are also kept as the objects they were built from, so that
+/// `inputs` returns e.g. spheres rather than trees.
 #[derive(Clone, KotoCopy, KotoType)]
-pub struct KUnion(Union);
+pub struct KUnion(Union, Vec<KObject>);
 
 impl KotoObject for KUnion {
     fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
@@ -176,7 +436,8 @@ impl KotoObject for KUnion {
 
 impl From<Union> for KUnion {
     fn from(tree: Union) -> Self {
-        Self(tree)
+        let inputs = tree_objects(&tree.input);
+        Self(tree, inputs)
     }
 }
 
@@ -196,7 +457,15 @@ impl fmt::Display for KUnion {
 impl KUnion {
     /// Create KotoObject representing fidget::shapes::Union
     pub fn new(a: Tree, b: Tree) -> KObject {
-        KObject::from(Self(Union { input: vec![a, b] }.into()))
+        KObject::from(Self::from(Union { input: vec![a, b] }))
+    }
+
+    /// Create KotoObject combining shape objects, which `inputs` returns
+    ///
+    /// Returns `None` if an object is not a shape.
+    pub fn from_inputs(inputs: Vec<KObject>) -> Option<KObject> {
+        let input = input_trees(&inputs)?;
+        Some(KObject::from(Self(Union { input }, inputs)))
     }
 
     /// Access the inner fidget Union struct
@@ -211,4 +480,102 @@ impl KUnion {
             self.inner(),
         )))))
     }
+
+    /// The shapes which are combined, as a list of the objects passed in
+    #[koto_get]
+    fn inputs(&self) -> runtime::Result<KValue> {
+        let inputs = self
+            .1
+            .iter()
+            .cloned()
+            .map(KValue::Object)
+            .collect::<Vec<_>>();
+        Ok(KValue::List(KList::from_slice(&inputs)))
+    }
+
+    /// Copy of the union with different inputs
+    #[koto_method]
+    fn with_inputs(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let inputs = match ctx.args {
+            [KValue::List(list)] if !list.data().is_empty() => list
+                .data()
+                .iter()
+                .map(|value| match value {
+                    KValue::Object(obj) => Some(obj.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        match inputs.and_then(Self::from_inputs) {
+            Some(obj) => Ok(obj.into()),
+            None => unexpected_args("|inputs: non-empty List of shapes|", ctx.args),
+        }
+    }
+
+    /// Copy of the union with another shape added to its inputs
+    #[koto_method]
+    fn add(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        let mut inputs = ctx.instance()?.1.clone();
+        inner.input.push(args.tree(0));
+        inputs.push(args.object(0));
+        Ok(Self(inner, inputs).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
+}
+
+/// Wraps trees in `Tree` objects, for inputs which were not built in Koto
+fn tree_objects(trees: &[Tree]) -> Vec<KObject> {
+    trees
+        .iter()
+        .map(|tree| KObject::from(KTree::from(tree.clone())))
+        .collect()
+}
+
+/// Converts shape objects to trees, or returns `None` if one isn't a shape
+fn input_trees(inputs: &[KObject]) -> Option<Vec<Tree>> {
+    inputs.iter().map(maybe_tree).collect()
 }
@@ -0,0 +1,81 @@
+use fidget::context::Tree;
+
+/// Axis used by deformations which operate around a single axis
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Parses an axis name (`"x"`, `"y"` or `"z"`)
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x" | "X" => Some(Self::X),
+            "y" | "Y" => Some(Self::Y),
+            "z" | "Z" => Some(Self::Z),
+            _ => None,
+        }
+    }
+}
+
+/// Rotates `a` and `b` by `-angle`, returning the remapped coordinates
+fn rotate(a: Tree, b: Tree, angle: Tree) -> (Tree, Tree) {
+    let (sin, cos) = (angle.clone().sin(), angle.cos());
+    (
+        cos.clone() * a.clone() + sin.clone() * b.clone(),
+        cos * b - sin * a,
+    )
+}
+
+/// Twists `shape` around `axis`, by `rate` radians per unit along that axis
+pub(crate) fn twist(shape: Tree, rate: f64, axis: Axis) -> Tree {
+    let (x, y, z) = Tree::axes();
+    match axis {
+        Axis::X => {
+            let (y2, z2) = rotate(y, z, x.clone() * rate);
+            shape.remap_xyz(x, y2, z2)
+        }
+        Axis::Y => {
+            let (z2, x2) = rotate(z, x, y.clone() * rate);
+            shape.remap_xyz(x2, y, z2)
+        }
+        Axis::Z => {
+            let (x2, y2) = rotate(x, y, z.clone() * rate);
+            shape.remap_xyz(x2, y2, z)
+        }
+    }
+}
+
+/// Bends `shape` in the `xy` plane, with curvature `k` along the `x` axis
+///
+/// This is the cheap bend, which does not preserve distances; keep `k` small
+/// relative to the shape's size.
+pub(crate) fn bend(shape: Tree, k: f64) -> Tree {
+    let (x, y, z) = Tree::axes();
+    let angle = x.clone() * -k;
+    let (x2, y2) = rotate(x, y, angle);
+    shape.remap_xyz(x2, y2, z)
+}
+
+/// Tapers `shape` along the `z` axis
+///
+/// The `x` and `y` extent is scaled by `1 + factor * z`, so the shape is
+/// unchanged at `z = 0`.  The field is undefined where that scale is zero.
+pub(crate) fn taper(shape: Tree, factor: f64) -> Tree {
+    let (x, y, z) = Tree::axes();
+    let scale = z.clone() * factor + 1.0;
+    shape.remap_xyz(x / scale.clone(), y / scale, z)
+}
+
+/// Adds a scalar displacement field to the value of `shape`
+pub(crate) fn displace(shape: Tree, field: Tree) -> Tree {
+    shape + field
+}
+
+/// Displaces the domain of `shape` by the vector field `(dx, dy, dz)`
+pub(crate) fn displace_xyz(shape: Tree, dx: Tree, dy: Tree, dz: Tree) -> Tree {
+    let (x, y, z) = Tree::axes();
+    shape.remap_xyz(x - dx, y - dy, z - dz)
+}
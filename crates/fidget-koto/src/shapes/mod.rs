@@ -1,7 +1,14 @@
 mod csg;
+mod deform;
+mod offset;
 mod primitives;
+mod repeat;
 mod transforms;
 
 pub use csg::{KDifference, KIntersection, KInverse, KUnion};
+pub(crate) use deform::{Axis, bend, displace, displace_xyz, taper, twist};
+pub(crate) use offset::{OffsetOp, offset, offset_method, onion, round_edges, shell};
 pub use primitives::{KCircle, KSphere};
+pub(crate) use repeat::{polar_array, repeat, repeat_limited};
 pub use transforms::{KMove, KScale};
+pub(crate) use transforms::{SCALE, TRANSLATION};
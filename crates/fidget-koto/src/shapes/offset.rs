@@ -0,0 +1,72 @@
+use fidget::context::Tree;
+use koto::{prelude::*, runtime};
+
+use crate::KTree;
+use crate::args::{Param, parse_args};
+
+/// Grows (`d > 0`) or shrinks (`d < 0`) `shape` by `d`
+///
+/// For an exact SDF, growing keeps the field exact; shrinking keeps it exact
+/// inside the shape but only a bound outside of it, near concave features.
+pub(crate) fn offset(shape: Tree, d: f64) -> Tree {
+    shape - d
+}
+
+/// Hollows out `shape`, leaving a wall of `thickness` inside its surface
+///
+/// The outer surface is unchanged.  For an exact SDF, the result is exact.
+pub(crate) fn shell(shape: Tree, thickness: f64) -> Tree {
+    (shape + thickness / 2.0).abs() - thickness / 2.0
+}
+
+/// Builds `layers` concentric shells of `thickness`, separated by gaps of the
+/// same thickness, going inwards from the surface of `shape`
+///
+/// For an exact SDF, the result is exact outside of the shells and a bound
+/// inside of them.
+pub(crate) fn onion(shape: Tree, thickness: f64, layers: f64) -> Tree {
+    let layers = layers.floor().max(1.0) as usize;
+    (0..layers)
+        .map(|i| shell(shape.clone() + 2.0 * thickness * i as f64, thickness))
+        .reduce(|a, b| a.min(b))
+        .unwrap()
+}
+
+/// Rounds the convex edges of `shape` with radius `r`
+///
+/// This also grows the shape by `r`, so it should be applied to a shape which
+/// has been built `r` smaller.  Edges are only rounded for an exact SDF; for
+/// other fields, this is the same as [`offset`].
+pub(crate) fn round_edges(shape: Tree, r: f64) -> Tree {
+    shape - r
+}
+
+/// Operation called through [`offset_method`]
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum OffsetOp {
+    Offset,
+    Shell,
+    Onion,
+    RoundEdges,
+}
+
+/// Shared implementation of the `offset`, `shell`, `onion` and `round_edges`
+/// methods of trees and shape objects
+pub(crate) fn offset_method(op: OffsetOp, shape: Tree, args: &[KValue]) -> runtime::Result<KValue> {
+    let number = |name| -> runtime::Result<f64> {
+        Ok(parse_args(args, &[&[Param::number(name)]])?.number(0))
+    };
+    let tree = match op {
+        OffsetOp::Offset => offset(shape, number("d")?),
+        OffsetOp::Shell => shell(shape, number("thickness")?),
+        OffsetOp::RoundEdges => round_edges(shape, number("r")?),
+        OffsetOp::Onion => {
+            let args = parse_args(
+                args,
+                &[&[Param::number("thickness"), Param::number("layers")]],
+            )?;
+            onion(shape, args.number(0), args.number(1))
+        }
+    };
+    Ok(KObject::from(KTree::from(tree)).into())
+}
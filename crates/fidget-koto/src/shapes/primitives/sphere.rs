@@ -6,6 +6,8 @@ use koto::{derive::*, prelude::*, runtime};
 use std::fmt;
 
 use crate::KTree;
+use crate::args::{Param, parse_args};
+use crate::utils::numbers_to_vec3;
 
 /// KotoObject wrapper for fidget Sphere
 #[derive(Clone, KotoCopy, KotoType)]
@@ -65,4 +67,78 @@ impl KSphere {
             self.inner(),
         )))))
     }
+
+    /// Radius of the sphere
+    #[koto_get]
+    fn radius(&self) -> runtime::Result<KValue> {
+        Ok(self.0.radius.into())
+    }
+
+    /// Copy of the sphere with a new radius
+    #[koto_method]
+    fn with_radius(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::number("radius")]])?;
+        let mut inner = ctx.instance()?.inner();
+        inner.radius = args.number(0);
+        Ok(Self(inner).into())
+    }
+
+    /// Center of the sphere, as a `Vec3`
+    #[koto_get]
+    fn center(&self) -> runtime::Result<KValue> {
+        let v = &self.0.center;
+        Ok(numbers_to_vec3(v.x, v.y, v.z))
+    }
+
+    /// Copy of the sphere with a new center
+    #[koto_method]
+    fn with_center(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::vec3("center")]])?;
+        let [x, y, z] = args.vec3(0);
+        let mut inner = ctx.instance()?.inner();
+        inner.center = Vec3 { x, y, z };
+        Ok(Self(inner).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
@@ -0,0 +1,72 @@
+use fidget::context::Tree;
+use std::f64::consts::TAU;
+
+/// Folds a single coordinate into a cell of width `spacing`, centered at 0
+///
+/// A `spacing` of `0.0` (or below) disables repetition along that axis.
+fn fold(axis: Tree, spacing: f64) -> Tree {
+    if spacing > 0.0 {
+        ((axis + spacing / 2.0).modulo(spacing)) - spacing / 2.0
+    } else {
+        axis
+    }
+}
+
+/// Folds a single coordinate into one of `count` cells of width `spacing`
+///
+/// The cells are centered around the origin; outside of them the outermost
+/// copy is extended, so the field stays continuous.
+fn fold_limited(axis: Tree, spacing: f64, count: f64) -> Tree {
+    let count = count.floor();
+    if spacing > 0.0 && count > 1.0 {
+        let half = (count - 1.0) / 2.0;
+        let index = (axis.clone() / spacing + half)
+            .round()
+            .max(0.0)
+            .min(count - 1.0);
+        axis - (index - half) * spacing
+    } else {
+        axis
+    }
+}
+
+/// Infinite repetition of `shape` along `x`, `y` and `z`
+///
+/// Each spacing component of `0.0` disables repetition along that axis.
+pub(crate) fn repeat(shape: Tree, spacing: [f64; 3]) -> Tree {
+    let (x, y, z) = Tree::axes();
+    shape.remap_xyz(
+        fold(x, spacing[0]),
+        fold(y, spacing[1]),
+        fold(z, spacing[2]),
+    )
+}
+
+/// Repetition of `shape` into a grid of `counts` copies, centered at the origin
+pub(crate) fn repeat_limited(shape: Tree, spacing: [f64; 3], counts: [f64; 3]) -> Tree {
+    let (x, y, z) = Tree::axes();
+    shape.remap_xyz(
+        fold_limited(x, spacing[0], counts[0]),
+        fold_limited(y, spacing[1], counts[1]),
+        fold_limited(z, spacing[2], counts[2]),
+    )
+}
+
+/// Repetition of `shape` into `n` copies arranged around the `z` axis
+///
+/// The copy which is not rotated is the one around the positive `x` axis.
+pub(crate) fn polar_array(shape: Tree, n: f64) -> Tree {
+    let n = n.floor();
+    if n < 2.0 {
+        return shape;
+    }
+    let (x, y, z) = Tree::axes();
+    let sector = TAU / n;
+    let radius = (x.clone().square() + y.clone().square()).sqrt();
+    let angle = fold(y.atan2(x), sector);
+    shape.remap_xyz(
+        radius.clone() * angle.clone().cos(),
+        radius * angle.sin(),
+        z,
+    )
+}
@@ -6,6 +6,16 @@ use koto::{derive::*, prelude::*, runtime};
 use std::fmt;
 
 use crate::KTree;
+use crate::args::{Param, parse_args};
+use crate::utils::numbers_to_vec3;
+
+/// Parameter for the translation of `move`, shared by the builtin and
+/// `with_translation` so that both accept the same map key
+pub(crate) const TRANSLATION: Param = Param::vec3("translation");
+
+/// Parameter for the factors of `scale`, shared by the builtin and
+/// `with_scale`
+pub(crate) const SCALE: Param = Param::vec3("scale").splat().pad(1.0);
 
 /// KotoObject wrapper for fidget Move
 #[derive(Clone, KotoCopy, KotoType)]
@@ -65,6 +75,80 @@ impl KMove {
             self.inner(),
         )))))
     }
+
+    /// The shape being moved
+    #[koto_get]
+    fn shape(&self) -> runtime::Result<KValue> {
+        Ok(KTree::from(self.0.shape.clone()).into())
+    }
+
+    /// Copy with a different shape being moved
+    #[koto_method]
+    fn with_shape(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        inner.shape = args.tree(0);
+        Ok(Self(inner).into())
+    }
+
+    /// Translation, as a `Vec3`
+    #[koto_get]
+    fn translation(&self) -> runtime::Result<KValue> {
+        let v = &self.0.offset;
+        Ok(numbers_to_vec3(v.x, v.y, v.z))
+    }
+
+    /// Copy with a new translation
+    #[koto_method]
+    fn with_translation(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[TRANSLATION]])?;
+        let [x, y, z] = args.vec3(0);
+        let mut inner = ctx.instance()?.inner();
+        inner.offset = Vec3 { x, y, z };
+        Ok(Self(inner).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
 
 /// KotoObject wrapper for fidget Scale
@@ -125,4 +209,78 @@ impl KScale {
             self.inner(),
         )))))
     }
+
+    /// The shape being scaled
+    #[koto_get]
+    fn shape(&self) -> runtime::Result<KValue> {
+        Ok(KTree::from(self.0.shape.clone()).into())
+    }
+
+    /// Copy with a different shape being scaled
+    #[koto_method]
+    fn with_shape(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[Param::shape("shape")]])?;
+        let mut inner = ctx.instance()?.inner();
+        inner.shape = args.tree(0);
+        Ok(Self(inner).into())
+    }
+
+    /// Scale factors per axis, as a `Vec3`
+    #[koto_get]
+    fn scale(&self) -> runtime::Result<KValue> {
+        let v = &self.0.scale;
+        Ok(numbers_to_vec3(v.x, v.y, v.z))
+    }
+
+    /// Copy with new scale factors
+    #[koto_method]
+    fn with_scale(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        let args = parse_args(ctx.args, &[&[SCALE]])?;
+        let [x, y, z] = args.vec3(0);
+        let mut inner = ctx.instance()?.inner();
+        inner.scale = Vec3 { x, y, z };
+        Ok(Self(inner).into())
+    }
+
+    /// Grow or shrink the shape, see the `offset` builtin
+    #[koto_method]
+    fn offset(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Offset)
+    }
+
+    /// Hollow out the shape, see the `shell` builtin
+    #[koto_method]
+    fn shell(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Shell)
+    }
+
+    /// Build concentric shells inside of the shape, see the `onion` builtin
+    #[koto_method]
+    fn onion(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, Onion)
+    }
+
+    /// Round the convex edges of the shape, see the `round_edges` builtin
+    #[koto_method]
+    fn round_edges(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        offset_fn!(ctx, RoundEdges)
+    }
+
+    /// Evaluate the shape at a point, see [`query`](crate::query)
+    #[koto_method]
+    fn eval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, eval_method)
+    }
+
+    /// Evaluate bounds of the shape over a box
+    #[koto_method]
+    fn interval(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, interval_method)
+    }
+
+    /// Evaluate the gradient of the shape at a point
+    #[koto_method]
+    fn gradient(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        query_fn!(ctx, gradient_method)
+    }
 }
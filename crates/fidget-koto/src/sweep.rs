@@ -0,0 +1,247 @@
+//! Sweeps and tubes along polylines and splines
+//!
+//! Paths are polylines, given as a list of points.  Splines are sampled into
+//! polylines with [`bezier`] and [`catmull_rom`], and the distance to every
+//! segment of the polyline is built as a [`Tree`].
+//!
+//! In Koto:
+//!
+//! ```koto
+//! path = catmull_rom [[-1, 0, 0], [0, 0.5, 0], [1, 0, 0.5]], 8
+//! draw tube path, 0.1
+//! ```
+
+use crate::KTree;
+use crate::args::{Param, parse_args};
+use fidget::context::Tree;
+use koto::prelude::*;
+
+/// Point in space
+pub type Point = [f64; 3];
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Point, b: Point) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Point, b: Point) -> Point {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: Point) -> Point {
+    let len = dot(a, a).sqrt();
+    [a[0] / len, a[1] / len, a[2] / len]
+}
+
+/// Returns the tree `dot(p - origin, dir)`, with `p` being `(x, y, z)`
+fn project(origin: Point, dir: Point) -> Tree {
+    let (x, y, z) = Tree::axes();
+    (x - origin[0]) * dir[0] + (y - origin[1]) * dir[1] + (z - origin[2]) * dir[2]
+}
+
+/// Distance to the point `a`
+fn point_distance(a: Point) -> Tree {
+    let (x, y, z) = Tree::axes();
+    ((x - a[0]).square() + (y - a[1]).square() + (z - a[2]).square()).sqrt()
+}
+
+/// Distance to the line segment from `a` to `b`, which must be distinct
+fn segment_distance(a: Point, b: Point) -> Tree {
+    let (x, y, z) = Tree::axes();
+    let ba = sub(b, a);
+    let h = (project(a, ba) / dot(ba, ba)).max(0.0).min(1.0);
+    let dx = x - a[0] - h.clone() * ba[0];
+    let dy = y - a[1] - h.clone() * ba[1];
+    let dz = z - a[2] - h * ba[2];
+    (dx.square() + dy.square() + dz.square()).sqrt()
+}
+
+/// Builds a tube of `radius` around the polyline `path`
+///
+/// The result is an exact SDF.  `path` must have at least one point; repeated
+/// points are skipped, and a path of a single point gives a sphere.
+pub fn tube(path: &[Point], radius: f64) -> Tree {
+    path.windows(2)
+        .filter(|w| w[0] != w[1])
+        .map(|w| segment_distance(w[0], w[1]))
+        .reduce(|a, b| a.min(b))
+        .unwrap_or_else(|| point_distance(path[0]))
+        - radius
+}
+
+/// Sweeps the 2D `profile` (in terms of `x` and `y`) along the polyline `path`
+///
+/// Each segment is an exact extrusion of the profile.  The profile frame is
+/// carried along the path without twisting; the profile's `y` axis starts out
+/// as close to `+z` as possible.  At sharp corners, neighbouring segments
+/// overlap or leave small gaps, so splines should be sampled finely.  `path`
+/// must have at least two distinct points.
+pub fn sweep(profile: Tree, path: &[Point]) -> Tree {
+    let mut normal: Option<Point> = None;
+    path.windows(2)
+        .filter(|w| w[0] != w[1])
+        .map(|w| {
+            let (a, b) = (w[0], w[1]);
+            let t = normalize(sub(b, a));
+            // Parallel-transport the previous normal, so the profile does not
+            // spin around the path
+            let n = match normal {
+                Some(n) => {
+                    let d = dot(n, t);
+                    let n = [n[0] - d * t[0], n[1] - d * t[1], n[2] - d * t[2]];
+                    if dot(n, n) > 1e-12 {
+                        normalize(n)
+                    } else {
+                        initial_normal(t)
+                    }
+                }
+                None => initial_normal(t),
+            };
+            normal = Some(n);
+            let binormal = cross(t, n);
+
+            let mid = [
+                (a[0] + b[0]) / 2.0,
+                (a[1] + b[1]) / 2.0,
+                (a[2] + b[2]) / 2.0,
+            ];
+            let half = dot(sub(b, a), t) / 2.0;
+            let u = project(mid, binormal);
+            let v = project(mid, n);
+            let s = project(mid, t);
+            let d2 = profile.remap_xyz(u, v, Tree::constant(0.0));
+            let w = s.abs() - half;
+            d2.clone().max(w.clone()).min(0.0) + (d2.max(0.0).square() + w.max(0.0).square()).sqrt()
+        })
+        .reduce(|a, b| a.min(b))
+        .unwrap()
+}
+
+/// Picks a normal for the tangent `t`, as close to `+z` as possible
+fn initial_normal(t: Point) -> Point {
+    let up = if t[2].abs() < 0.999 {
+        [0.0, 0.0, 1.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let d = dot(up, t);
+    normalize([up[0] - d * t[0], up[1] - d * t[1], up[2] - d * t[2]])
+}
+
+/// Samples a chain of cubic Bézier curves into a polyline
+///
+/// `points` holds the start point, then two control points and an end point
+/// for every curve (so `3 * n + 1` points for `n` curves); trailing points
+/// which do not form a full curve are ignored.  Each curve is split into
+/// `segments` segments.
+pub fn bezier(points: &[Point], segments: usize) -> Vec<Point> {
+    let segments = segments.max(1);
+    let mut out = vec![];
+    if let Some(first) = points.first() {
+        out.push(*first);
+    }
+    for curve in points.windows(4).step_by(3) {
+        for i in 1..=segments {
+            let t = i as f64 / segments as f64;
+            let s = 1.0 - t;
+            let w = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+            out.push(std::array::from_fn(|k| {
+                (0..4).map(|j| w[j] * curve[j][k]).sum()
+            }));
+        }
+    }
+    out
+}
+
+/// Samples a uniform Catmull-Rom spline through `points` into a polyline
+///
+/// The spline passes through every point; each span between two points is
+/// split into `segments` segments.
+pub fn catmull_rom(points: &[Point], segments: usize) -> Vec<Point> {
+    let segments = segments.max(1);
+    let mut out = vec![];
+    if let Some(first) = points.first() {
+        out.push(*first);
+    }
+    for i in 0..points.len().saturating_sub(1) {
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        for j in 1..=segments {
+            let t = j as f64 / segments as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            out.push(std::array::from_fn(|k| {
+                0.5 * (2.0 * p1[k]
+                    + (p2[k] - p0[k]) * t
+                    + (2.0 * p0[k] - 5.0 * p1[k] + 4.0 * p2[k] - p3[k]) * t2
+                    + (3.0 * p1[k] - p0[k] - 3.0 * p2[k] + p3[k]) * t3)
+            }));
+        }
+    }
+    out
+}
+
+fn points_to_koto(points: Vec<Point>) -> KValue {
+    let points = points
+        .into_iter()
+        .map(|p| KValue::Tuple(p.map(KValue::from).to_vec().into()))
+        .collect::<Vec<_>>();
+    KValue::List(KList::from_slice(&points))
+}
+
+/// Adds the sweep and spline functions to the given module
+pub(crate) fn add_sweep_fns(module: &KMap) {
+    module.add_fn("tube", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[Param::points("path"), Param::number("radius")]],
+        )?;
+        if args.points(0).is_empty() {
+            return unexpected_args(
+                "|path: non-empty List of points, radius: Number|",
+                ctx.args(),
+            );
+        }
+        Ok(KTree::from(tube(args.points(0), args.number(1))).into())
+    });
+
+    module.add_fn("sweep", |ctx| {
+        let args = parse_args(
+            ctx.args(),
+            &[&[Param::shape("profile"), Param::points("path")]],
+        )?;
+        if !args.points(1).windows(2).any(|w| w[0] != w[1]) {
+            return unexpected_args(
+                "|profile: Tree, path: List of at least 2 distinct points|",
+                ctx.args(),
+            );
+        }
+        Ok(KTree::from(sweep(args.tree(0), args.points(1))).into())
+    });
+
+    macro_rules! add_spline_fn {
+        ($name_string:literal, $name:ident) => {
+            module.add_fn($name_string, |ctx| {
+                let args = parse_args(
+                    ctx.args(),
+                    &[&[
+                        Param::points("points"),
+                        Param::number("segments").or_number(16.0),
+                    ]],
+                )?;
+                let segments = args.number(1).max(1.0) as usize;
+                Ok(points_to_koto($name(args.points(0), segments)))
+            });
+        };
+    }
+
+    add_spline_fn!("bezier", bezier);
+    add_spline_fn!("catmull_rom", catmull_rom);
+}
@@ -1,6 +1,8 @@
 use super::{KCircle, KDifference, KIntersection, KInverse, KMove, KScale, KSphere, KTree, KUnion};
+use crate::vec::{KVec2, KVec3, Scalar};
 use fidget::context::Tree;
-use koto::runtime::KObject;
+use koto::runtime::{KObject, KValue};
+use std::fmt::Write;
 
 pub(crate) fn maybe_tree(obj: &KObject) -> Option<Tree> {
     if obj.is_a::<KTree>() {
@@ -34,3 +36,102 @@ pub(crate) fn maybe_tree(obj: &KObject) -> Option<Tree> {
         None
     }
 }
+
+pub(crate) fn maybe_tree_or_number(value: &KValue) -> Option<Tree> {
+    match value {
+        KValue::Object(obj) => maybe_tree(obj),
+        KValue::Number(num) => Some(Tree::constant(f64::from(num))),
+        _ => None,
+    }
+}
+
+/// Converts a list, tuple or vector of 2 or 3 numbers into a point, with `z = 0` for
+/// 2D points
+pub(crate) fn maybe_point(value: &KValue) -> Option<[f64; 3]> {
+    let numbers = match value {
+        KValue::List(list) => list
+            .data()
+            .iter()
+            .map(maybe_number)
+            .collect::<Option<Vec<_>>>(),
+        KValue::Tuple(tuple) => tuple.iter().map(maybe_number).collect::<Option<Vec<_>>>(),
+        KValue::Object(obj) if obj.is_a::<KVec2>() => {
+            let v = obj.cast::<KVec2>().ok()?;
+            v.0.iter()
+                .map(Scalar::as_number)
+                .collect::<Option<Vec<_>>>()
+        }
+        KValue::Object(obj) if obj.is_a::<KVec3>() => {
+            let v = obj.cast::<KVec3>().ok()?;
+            v.0.iter()
+                .map(Scalar::as_number)
+                .collect::<Option<Vec<_>>>()
+        }
+        _ => None,
+    }?;
+    match numbers.as_slice() {
+        [x, y] => Some([*x, *y, 0.0]),
+        [x, y, z] => Some([*x, *y, *z]),
+        _ => None,
+    }
+}
+
+/// Converts a list or tuple of points into a `Vec`
+pub(crate) fn maybe_points(value: &KValue) -> Option<Vec<[f64; 3]>> {
+    match value {
+        KValue::List(list) => list.data().iter().map(maybe_point).collect(),
+        KValue::Tuple(tuple) => tuple.iter().map(maybe_point).collect(),
+        _ => None,
+    }
+}
+
+fn maybe_number(value: &KValue) -> Option<f64> {
+    match value {
+        KValue::Number(num) => Some(f64::from(num)),
+        _ => None,
+    }
+}
+
+/// Converts numbers into a Koto `Vec2`
+pub(crate) fn numbers_to_vec2(x: f64, y: f64) -> KValue {
+    KValue::Object(KVec2::new(Scalar::Number(x), Scalar::Number(y)))
+}
+
+/// Converts numbers into a Koto `Vec3`
+pub(crate) fn numbers_to_vec3(x: f64, y: f64, z: f64) -> KValue {
+    KValue::Object(KVec3::new(
+        Scalar::Number(x),
+        Scalar::Number(y),
+        Scalar::Number(z),
+    ))
+}
+
+/// Escapes text for XML attributes and text
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes text for JSON strings
+#[cfg(feature = "mesh")]
+pub(crate) fn escape_json(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,583 @@
+//! `Vec2` and `Vec3` Koto types
+//!
+//! Vector components are either numbers or [`Tree`]s, so the same type is
+//! used for fixed positions (`vec3 0, 0, 1`) and for vector fields
+//! (`vec3 x, y, z`).  Operations on vectors with numeric components stay
+//! numeric; as soon as a [`Tree`] is involved, the result is a [`Tree`].
+
+use crate::KTree;
+use crate::utils::maybe_tree;
+use fidget::context::Tree;
+use koto::{derive::*, prelude::*, runtime};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Vector component, which is either a number or a [`Tree`]
+#[derive(Clone)]
+pub enum Scalar {
+    /// Constant value
+    Number(f64),
+    /// Value which depends on the evaluation point
+    Tree(Tree),
+}
+
+impl Scalar {
+    /// Converts a Koto number or tree into a scalar
+    pub fn from_koto(value: &KValue) -> Option<Self> {
+        match value {
+            KValue::Number(num) => Some(Self::Number(f64::from(num))),
+            KValue::Object(obj) => maybe_tree(obj).map(Self::Tree),
+            _ => None,
+        }
+    }
+
+    /// Converts the scalar into a Koto number or tree
+    pub fn to_koto(&self) -> KValue {
+        match self {
+            Self::Number(num) => KValue::from(*num),
+            Self::Tree(tree) => KTree::from(tree.clone()).into(),
+        }
+    }
+
+    /// Returns the scalar as a tree, wrapping numbers in a constant
+    pub fn into_tree(self) -> Tree {
+        match self {
+            Self::Number(num) => Tree::constant(num),
+            Self::Tree(tree) => tree,
+        }
+    }
+
+    /// Returns the value if the scalar is a number
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(num) => Some(*num),
+            Self::Tree(_) => None,
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        match self {
+            Self::Number(num) => Self::Number(num.sqrt()),
+            Self::Tree(tree) => Self::Tree(tree.sqrt()),
+        }
+    }
+}
+
+macro_rules! scalar_op {
+    ($trait:ident, $fn:ident) => {
+        impl $trait for Scalar {
+            type Output = Scalar;
+
+            fn $fn(self, other: Scalar) -> Scalar {
+                match (self, other) {
+                    (Self::Number(a), Self::Number(b)) => Self::Number(a.$fn(b)),
+                    (a, b) => Self::Tree(a.into_tree().$fn(b.into_tree())),
+                }
+            }
+        }
+    };
+}
+
+scalar_op!(Add, add);
+scalar_op!(Sub, sub);
+scalar_op!(Mul, mul);
+scalar_op!(Div, div);
+
+impl Neg for Scalar {
+    type Output = Scalar;
+
+    fn neg(self) -> Scalar {
+        match self {
+            Self::Number(num) => Self::Number(-num),
+            Self::Tree(tree) => Self::Tree(-tree),
+        }
+    }
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(num) => write!(f, "{num}"),
+            Self::Tree(tree) => write!(f, "{}", KTree::from(tree.clone())),
+        }
+    }
+}
+
+fn zip<const N: usize>(
+    a: &[Scalar; N],
+    b: &[Scalar; N],
+    f: impl Fn(Scalar, Scalar) -> Scalar,
+) -> [Scalar; N] {
+    std::array::from_fn(|i| f(a[i].clone(), b[i].clone()))
+}
+
+fn dot<const N: usize>(a: &[Scalar; N], b: &[Scalar; N]) -> Scalar {
+    zip(a, b, Scalar::mul)
+        .into_iter()
+        .reduce(Scalar::add)
+        .unwrap()
+}
+
+fn length<const N: usize>(a: &[Scalar; N]) -> Scalar {
+    dot(a, a).sqrt()
+}
+
+fn normalize<const N: usize>(a: &[Scalar; N]) -> [Scalar; N] {
+    let len = length(a);
+    a.clone().map(|c| c / len.clone())
+}
+
+fn lerp<const N: usize>(a: &[Scalar; N], b: &[Scalar; N], t: Scalar) -> [Scalar; N] {
+    zip(a, b, |a, b| a.clone() + (b - a) * t.clone())
+}
+
+fn cross(a: &[Scalar; 3], b: &[Scalar; 3]) -> [Scalar; 3] {
+    let c = |i: usize, j: usize| a[i].clone() * b[j].clone() - a[j].clone() * b[i].clone();
+    [c(1, 2), c(2, 0), c(0, 1)]
+}
+
+/// Parses `N` scalars from Koto values
+fn scalars<const N: usize>(values: &[KValue]) -> Option<[Scalar; N]> {
+    if values.len() != N {
+        return None;
+    }
+    let scalars = values
+        .iter()
+        .map(Scalar::from_koto)
+        .collect::<Option<Vec<_>>>()?;
+    scalars.try_into().ok()
+}
+
+/// Applies a vector/vector or vector/scalar operation, returning a new vector
+macro_rules! vec_binary_op {
+    ($self:ident, $other:expr, $op:expr, $reverse:expr) => {{
+        let op = $op;
+        match $other {
+            KValue::Object(obj) if obj.is_a::<Self>() => {
+                let other = obj.cast::<Self>()?;
+                let result = if $reverse {
+                    zip(&other.0, &$self.0, op)
+                } else {
+                    zip(&$self.0, &other.0, op)
+                };
+                Ok(Self(result).into())
+            }
+            other => match Scalar::from_koto(other) {
+                Some(s) => {
+                    let result = $self.0.clone().map(|c| {
+                        if $reverse {
+                            op(s.clone(), c)
+                        } else {
+                            op(c, s.clone())
+                        }
+                    });
+                    Ok(Self(result).into())
+                }
+                None => unexpected_type("Vector, Number or Tree", other),
+            },
+        }
+    }};
+}
+
+/// KotoObject for a 2D vector with number or Tree components
+#[derive(Clone, KotoCopy, KotoType)]
+#[koto(type_name = "Vec2")]
+pub struct KVec2(pub [Scalar; 2]);
+
+impl KotoObject for KVec2 {
+    fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
+        ctx.append(self.to_string());
+        Ok(())
+    }
+
+    fn index(&self, index: &KValue) -> runtime::Result<KValue> {
+        match index {
+            KValue::Number(i) => match usize::try_from(i64::from(i))
+                .ok()
+                .and_then(|i| self.0.get(i))
+            {
+                Some(c) => Ok(c.to_koto()),
+                None => unexpected_type("index 0 or 1", index),
+            },
+            unexpected => unexpected_type("Number", unexpected),
+        }
+    }
+
+    fn negate(&self) -> runtime::Result<KValue> {
+        Ok(Self(self.0.clone().map(Scalar::neg)).into())
+    }
+
+    fn add(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::add, false)
+    }
+
+    fn add_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::add, true)
+    }
+
+    fn subtract(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::sub, false)
+    }
+
+    fn subtract_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::sub, true)
+    }
+
+    fn multiply(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::mul, false)
+    }
+
+    fn multiply_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::mul, true)
+    }
+
+    fn divide(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::div, false)
+    }
+
+    fn divide_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::div, true)
+    }
+}
+
+impl From<KVec2> for KValue {
+    fn from(obj: KVec2) -> Self {
+        KObject::from(obj).into()
+    }
+}
+
+impl fmt::Display for KVec2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Vec2{{x: {}, y: {}}}", self.0[0], self.0[1])
+    }
+}
+
+#[koto_impl]
+impl KVec2 {
+    /// Create KotoObject representing a 2D vector
+    pub fn new(x: Scalar, y: Scalar) -> KObject {
+        KObject::from(Self([x, y]))
+    }
+
+    /// Access the vector components
+    pub fn inner(&self) -> [Scalar; 2] {
+        self.0.clone()
+    }
+
+    #[koto_method]
+    fn x(&self) -> runtime::Result<KValue> {
+        Ok(self.0[0].to_koto())
+    }
+
+    #[koto_method]
+    fn y(&self) -> runtime::Result<KValue> {
+        Ok(self.0[1].to_koto())
+    }
+
+    #[koto_method]
+    fn dot(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        match ctx.args {
+            [KValue::Object(obj)] if obj.is_a::<Self>() => {
+                let other = obj.cast::<Self>()?;
+                Ok(dot(&ctx.instance()?.0, &other.0).to_koto())
+            }
+            unexpected => unexpected_args("|Vec2|", unexpected),
+        }
+    }
+
+    #[koto_method]
+    fn length(&self) -> runtime::Result<KValue> {
+        Ok(length(&self.0).to_koto())
+    }
+
+    #[koto_method]
+    fn normalize(&self) -> runtime::Result<KValue> {
+        Ok(Self(normalize(&self.0)).into())
+    }
+
+    #[koto_method]
+    fn lerp(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        match ctx.args {
+            [KValue::Object(obj), t] if obj.is_a::<Self>() => match Scalar::from_koto(t) {
+                Some(t) => {
+                    let other = obj.cast::<Self>()?;
+                    Ok(Self(lerp(&ctx.instance()?.0, &other.0, t)).into())
+                }
+                None => unexpected_args("|Vec2, Number|Tree|", ctx.args),
+            },
+            unexpected => unexpected_args("|Vec2, Number|Tree|", unexpected),
+        }
+    }
+}
+
+/// KotoObject for a 3D vector with number or Tree components
+#[derive(Clone, KotoCopy, KotoType)]
+#[koto(type_name = "Vec3")]
+pub struct KVec3(pub [Scalar; 3]);
+
+impl KotoObject for KVec3 {
+    fn display(&self, ctx: &mut DisplayContext) -> runtime::Result<()> {
+        ctx.append(self.to_string());
+        Ok(())
+    }
+
+    fn index(&self, index: &KValue) -> runtime::Result<KValue> {
+        match index {
+            KValue::Number(i) => match usize::try_from(i64::from(i))
+                .ok()
+                .and_then(|i| self.0.get(i))
+            {
+                Some(c) => Ok(c.to_koto()),
+                None => unexpected_type("index 0, 1 or 2", index),
+            },
+            unexpected => unexpected_type("Number", unexpected),
+        }
+    }
+
+    fn negate(&self) -> runtime::Result<KValue> {
+        Ok(Self(self.0.clone().map(Scalar::neg)).into())
+    }
+
+    fn add(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::add, false)
+    }
+
+    fn add_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::add, true)
+    }
+
+    fn subtract(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::sub, false)
+    }
+
+    fn subtract_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::sub, true)
+    }
+
+    fn multiply(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::mul, false)
+    }
+
+    fn multiply_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::mul, true)
+    }
+
+    fn divide(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::div, false)
+    }
+
+    fn divide_rhs(&self, other: &KValue) -> runtime::Result<KValue> {
+        vec_binary_op!(self, other, Scalar::div, true)
+    }
+}
+
+impl From<KVec3> for KValue {
+    fn from(obj: KVec3) -> Self {
+        KObject::from(obj).into()
+    }
+}
+
+impl fmt::Display for KVec3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Vec3{{x: {}, y: {}, z: {}}}",
+            self.0[0], self.0[1], self.0[2]
+        )
+    }
+}
+
+#[koto_impl]
+impl KVec3 {
+    /// Create KotoObject representing a 3D vector
+    pub fn new(x: Scalar, y: Scalar, z: Scalar) -> KObject {
+        KObject::from(Self([x, y, z]))
+    }
+
+    /// Access the vector components
+    pub fn inner(&self) -> [Scalar; 3] {
+        self.0.clone()
+    }
+
+    #[koto_method]
+    fn x(&self) -> runtime::Result<KValue> {
+        Ok(self.0[0].to_koto())
+    }
+
+    #[koto_method]
+    fn y(&self) -> runtime::Result<KValue> {
+        Ok(self.0[1].to_koto())
+    }
+
+    #[koto_method]
+    fn z(&self) -> runtime::Result<KValue> {
+        Ok(self.0[2].to_koto())
+    }
+
+    #[koto_method]
+    fn dot(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        match ctx.args {
+            [KValue::Object(obj)] if obj.is_a::<Self>() => {
+                let other = obj.cast::<Self>()?;
+                Ok(dot(&ctx.instance()?.0, &other.0).to_koto())
+            }
+            unexpected => unexpected_args("|Vec3|", unexpected),
+        }
+    }
+
+    #[koto_method]
+    fn cross(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        match ctx.args {
+            [KValue::Object(obj)] if obj.is_a::<Self>() => {
+                let other = obj.cast::<Self>()?;
+                Ok(Self(cross(&ctx.instance()?.0, &other.0)).into())
+            }
+            unexpected => unexpected_args("|Vec3|", unexpected),
+        }
+    }
+
+    #[koto_method]
+    fn length(&self) -> runtime::Result<KValue> {
+        Ok(length(&self.0).to_koto())
+    }
+
+    #[koto_method]
+    fn normalize(&self) -> runtime::Result<KValue> {
+        Ok(Self(normalize(&self.0)).into())
+    }
+
+    #[koto_method]
+    fn lerp(ctx: MethodContext<Self>) -> runtime::Result<KValue> {
+        match ctx.args {
+            [KValue::Object(obj), t] if obj.is_a::<Self>() => match Scalar::from_koto(t) {
+                Some(t) => {
+                    let other = obj.cast::<Self>()?;
+                    Ok(Self(lerp(&ctx.instance()?.0, &other.0, t)).into())
+                }
+                None => unexpected_args("|Vec3, Number|Tree|", ctx.args),
+            },
+            unexpected => unexpected_args("|Vec3, Number|Tree|", unexpected),
+        }
+    }
+}
+
+/// Adds the vector constructors and functions to the given module
+pub(crate) fn add_vec_fns(module: &KMap) {
+    module.add_fn("vec2", |ctx| {
+        let args = ctx.args();
+        if let [value] = args {
+            if let Some(s) = Scalar::from_koto(value) {
+                return Ok(KVec2([s.clone(), s]).into());
+            }
+        }
+        match scalars::<2>(args) {
+            Some(c) => Ok(KVec2(c).into()),
+            None => unexpected_args("|Number|Tree| or |Number|Tree, Number|Tree|", args),
+        }
+    });
+
+    module.add_fn("vec3", |ctx| {
+        let args = ctx.args();
+        if let [value] = args {
+            if let Some(s) = Scalar::from_koto(value) {
+                return Ok(KVec3([s.clone(), s.clone(), s]).into());
+            }
+        }
+        match scalars::<3>(args) {
+            Some(c) => Ok(KVec3(c).into()),
+            None => unexpected_args(
+                "|Number|Tree| or |Number|Tree, Number|Tree, Number|Tree|",
+                args,
+            ),
+        }
+    });
+
+    module.add_fn("dot", |ctx| match ctx.args() {
+        [KValue::Object(a), KValue::Object(b)] if a.is_a::<KVec2>() && b.is_a::<KVec2>() => {
+            Ok(dot(&a.cast::<KVec2>()?.0, &b.cast::<KVec2>()?.0).to_koto())
+        }
+        [KValue::Object(a), KValue::Object(b)] if a.is_a::<KVec3>() && b.is_a::<KVec3>() => {
+            Ok(dot(&a.cast::<KVec3>()?.0, &b.cast::<KVec3>()?.0).to_koto())
+        }
+        unexpected => unexpected_args("|Vec2, Vec2| or |Vec3, Vec3|", unexpected),
+    });
+
+    module.add_fn("cross", |ctx| match ctx.args() {
+        [KValue::Object(a), KValue::Object(b)] if a.is_a::<KVec3>() && b.is_a::<KVec3>() => {
+            Ok(KVec3(cross(&a.cast::<KVec3>()?.0, &b.cast::<KVec3>()?.0)).into())
+        }
+        unexpected => unexpected_args("|Vec3, Vec3|", unexpected),
+    });
+
+    module.add_fn("length", |ctx| match ctx.args() {
+        [KValue::Object(a)] if a.is_a::<KVec2>() => Ok(length(&a.cast::<KVec2>()?.0).to_koto()),
+        [KValue::Object(a)] if a.is_a::<KVec3>() => Ok(length(&a.cast::<KVec3>()?.0).to_koto()),
+        unexpected => unexpected_args("|Vec2| or |Vec3|", unexpected),
+    });
+
+    module.add_fn("normalize", |ctx| match ctx.args() {
+        [KValue::Object(a)] if a.is_a::<KVec2>() => {
+            Ok(KVec2(normalize(&a.cast::<KVec2>()?.0)).into())
+        }
+        [KValue::Object(a)] if a.is_a::<KVec3>() => {
+            Ok(KVec3(normalize(&a.cast::<KVec3>()?.0)).into())
+        }
+        unexpected => unexpected_args("|Vec2| or |Vec3|", unexpected),
+    });
+
+    // Also accepts scalars, so that this doesn't hide the `lerp` from the
+    // `fidget` module when its functions are added to the prelude
+    module.add_fn("lerp", |ctx| {
+        let args = ctx.args();
+        let expected = "|Vec2, Vec2, Number|Tree|, |Vec3, Vec3, Number|Tree| or \
+                        |Number|Tree, Number|Tree, Number|Tree|";
+        match args {
+            [KValue::Object(a), KValue::Object(b), t] if a.is_a::<KVec2>() && b.is_a::<KVec2>() => {
+                match Scalar::from_koto(t) {
+                    Some(t) => {
+                        Ok(KVec2(lerp(&a.cast::<KVec2>()?.0, &b.cast::<KVec2>()?.0, t)).into())
+                    }
+                    None => unexpected_args(expected, args),
+                }
+            }
+            [KValue::Object(a), KValue::Object(b), t] if a.is_a::<KVec3>() && b.is_a::<KVec3>() => {
+                match Scalar::from_koto(t) {
+                    Some(t) => {
+                        Ok(KVec3(lerp(&a.cast::<KVec3>()?.0, &b.cast::<KVec3>()?.0, t)).into())
+                    }
+                    None => unexpected_args(expected, args),
+                }
+            }
+            [a, b, t] => match (
+                Scalar::from_koto(a),
+                Scalar::from_koto(b),
+                Scalar::from_koto(t),
+            ) {
+                (Some(a), Some(b), Some(t)) => Ok((a.clone() + (b - a) * t).to_koto()),
+                _ => unexpected_args(expected, args),
+            },
+            _ => unexpected_args(expected, args),
+        }
+    });
+}
+
+/// Replaces every `Vec2` or `Vec3` in `args` with its components
+///
+/// This lets builtins which take scalar pairs or triples accept vectors.
+pub(crate) fn flatten_vectors(args: &[KValue]) -> Vec<KValue> {
+    let mut out = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            KValue::Object(obj) if obj.is_a::<KVec2>() => {
+                let v = obj.cast::<KVec2>().unwrap();
+                out.extend(v.0.iter().map(Scalar::to_koto));
+            }
+            KValue::Object(obj) if obj.is_a::<KVec3>() => {
+                let v = obj.cast::<KVec3>().unwrap();
+                out.extend(v.0.iter().map(Scalar::to_koto));
+            }
+            other => out.push(other.clone()),
+        }
+    }
+    out
+}
@@ -0,0 +1,220 @@
+//! Camera hints recorded by scripts
+//!
+//! A script can suggest how it should be looked at with `view`:
+//!
+//! ```koto
+//! view {scale: 0.05, pitch: 80, roll: 130, center: [0, 0, -14], projection: "isometric"}
+//! ```
+//!
+//! The hints end up in [`ScriptContext::view`](crate::ScriptContext::view).
+//! Hosts are free to ignore them, or to use them as the initial camera.  The
+//! transforms follow `fidget-cli`: the model is translated by `center`, scaled
+//! by `scale`, then rotated by `pitch`, `yaw` and `roll` (in degrees, about the
+//! X, Y and Z axes).  Renderers then look at the `[-1, 1]` cube, with +Z
+//! pointing towards the viewer.
+//!
+//! Interactive hosts should seed their camera with [`ViewHints::view_2d`] or
+//! [`ViewHints::view_3d`], which carry `scale` and `center`, and apply the
+//! rest of the hints with [`ViewHints::orient_3d`].
+
+use fidget::{
+    context::Tree,
+    render::{View2, View3},
+};
+
+/// Projection used for 3D views
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Orthographic projection
+    Isometric,
+    /// Perspective projection of the given strength
+    ///
+    /// Points at screen depth `z` are scaled by `1 / (1 - strength * z)`.
+    Perspective(f64),
+}
+
+/// Camera hints, which are all optional
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ViewHints {
+    /// Scale applied to the model, after translation
+    pub scale: Option<f64>,
+    /// Rotation about the X axis, in degrees
+    pub pitch: Option<f64>,
+    /// Rotation about the Y axis, in degrees
+    pub yaw: Option<f64>,
+    /// Rotation about the Z axis, in degrees
+    pub roll: Option<f64>,
+    /// Translation applied to the model
+    pub center: Option<[f64; 3]>,
+    /// Projection for 3D views
+    pub projection: Option<Projection>,
+    /// Factor by which depth is squashed before the perspective is applied
+    pub zflatten: Option<f64>,
+}
+
+impl ViewHints {
+    /// Checks whether no hint is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Replaces hints with the ones which are set in `other`
+    pub fn merge(&mut self, other: &ViewHints) {
+        let ViewHints {
+            scale,
+            pitch,
+            yaw,
+            roll,
+            center,
+            projection,
+            zflatten,
+        } = other.clone();
+        self.scale = scale.or(self.scale);
+        self.pitch = pitch.or(self.pitch);
+        self.yaw = yaw.or(self.yaw);
+        self.roll = roll.or(self.roll);
+        self.center = center.or(self.center);
+        self.projection = projection.or(self.projection);
+        self.zflatten = zflatten.or(self.zflatten);
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale.unwrap_or(1.0)
+    }
+
+    fn center(&self) -> [f64; 3] {
+        self.center.unwrap_or([0.0; 3])
+    }
+
+    /// Returns the rotation from model to screen coordinates, row by row
+    ///
+    /// This is `Rx(pitch) * Ry(yaw) * Rz(roll)`.
+    fn rotation(&self) -> [[f64; 3]; 3] {
+        let angle = |a: Option<f64>| a.unwrap_or(0.0).to_radians().sin_cos();
+        let (sx, cx) = angle(self.pitch);
+        let (sy, cy) = angle(self.yaw);
+        let (sz, cz) = angle(self.roll);
+        [
+            [cy * cz, -cy * sz, sy],
+            [sx * sy * cz + cx * sz, cx * cz - sx * sy * sz, -sx * cy],
+            [sx * sz - cx * sy * cz, cx * sy * sz + sx * cz, cx * cy],
+        ]
+    }
+
+    /// Returns the region of the XY plane shown by the view, for contouring
+    ///
+    /// Only `scale` and `center` apply.
+    pub fn contour_bounds(&self) -> crate::contour::Bounds {
+        let [x, y, _] = self.center();
+        crate::contour::Bounds {
+            center: [-x as f32, -y as f32],
+            size: (1.0 / self.scale()) as f32,
+        }
+    }
+
+    /// Returns the region shown by the view, for meshing
+    ///
+    /// Only `scale` and `center` apply.
+    #[cfg(feature = "mesh")]
+    pub fn mesh_bounds(&self) -> crate::mesh::Bounds {
+        let [x, y, z] = self.center();
+        crate::mesh::Bounds {
+            center: [-x as f32, -y as f32, -z as f32],
+            size: (1.0 / self.scale()) as f32,
+        }
+    }
+
+    /// Returns a 2D view with the hints' `scale` and `center`
+    ///
+    /// This shows the same region as [`transform_2d`](Self::transform_2d),
+    /// but in the plane `z = 0` rather than at the depth of `center`.
+    pub fn view_2d(&self) -> View2 {
+        let [x, y, _] = self.center();
+        View2::from_center_and_scale([-x as f32, -y as f32].into(), (1.0 / self.scale()) as f32)
+    }
+
+    /// Returns a 3D view with the hints' `scale` and `center`
+    ///
+    /// Rendering [`orient_3d`](Self::orient_3d) with this view gives the same
+    /// picture as [`transform_3d`](Self::transform_3d) with the default view.
+    pub fn view_3d(&self) -> View3 {
+        let [x, y, z] = self.center();
+        View3::from_center_and_scale(
+            [-x as f32, -y as f32, -z as f32].into(),
+            (1.0 / self.scale()) as f32,
+        )
+    }
+
+    /// Remaps a 3D tree for rendering with [`view_3d`](Self::view_3d)
+    ///
+    /// This applies the hints which fidget's views can't express: rotation,
+    /// projection and `zflatten`.  Trees are returned as-is if none are set.
+    pub fn orient_3d(&self, tree: &Tree) -> Tree {
+        let unoriented = ViewHints {
+            scale: self.scale,
+            center: self.center,
+            ..Default::default()
+        };
+        if *self == unoriented {
+            return tree.clone();
+        }
+        // Undo the view, going back to screen coordinates
+        let [cx, cy, cz] = self.center();
+        let scale = self.scale();
+        self.transform_3d(tree).remap_xyz(
+            (Tree::x() + cx) * scale,
+            (Tree::y() + cy) * scale,
+            (Tree::z() + cz) * scale,
+        )
+    }
+
+    /// Remaps a 2D tree from screen to model coordinates
+    ///
+    /// Only `scale` and `center` apply; the screen is the XY plane.
+    pub fn transform_2d(&self, tree: &Tree) -> Tree {
+        if self.is_empty() {
+            return tree.clone();
+        }
+        let [cx, cy, cz] = self.center();
+        let scale = self.scale();
+        tree.remap_xyz(
+            Tree::x() / scale - cx,
+            Tree::y() / scale - cy,
+            Tree::constant(-cz),
+        )
+    }
+
+    /// Remaps a 3D tree from screen to model coordinates
+    pub fn transform_3d(&self, tree: &Tree) -> Tree {
+        if self.is_empty() {
+            return tree.clone();
+        }
+        let (x, y, z) = (Tree::x(), Tree::y(), Tree::z());
+
+        // Undo the perspective divide, which shrinks distant points
+        let (x, y) = match self.projection {
+            Some(Projection::Perspective(p)) => {
+                let w = Tree::constant(1.0) - z.clone() * p;
+                (x * w.clone(), y * w)
+            }
+            _ => (x, y),
+        };
+        let screen = [x, y, z * self.zflatten.unwrap_or(1.0)];
+
+        // The inverse of a rotation is its transpose
+        let r = self.rotation();
+        let center = self.center();
+        let scale = self.scale();
+        let axis = |i: usize| {
+            let mut out = Tree::constant(-center[i]);
+            for (j, s) in screen.iter().enumerate() {
+                let k = r[j][i] / scale;
+                if k != 0.0 {
+                    out = out + s.clone() * k;
+                }
+            }
+            out
+        };
+        tree.remap_xyz(axis(0), axis(1), axis(2))
+    }
+}
@@ -12,10 +12,10 @@ use notify::Watcher;
 
 use fidget::{
     gui::{Canvas2, Canvas3, CursorState, DragMode},
-    render::{GeometryPixel, ImageRenderConfig, View2, View3, VoxelRenderConfig},
+    render::GeometryPixel,
 };
 
-use fidget_koto::ScriptContext;
+use fidget_koto::{ScriptContext, render, view::ViewHints};
 
 use std::{error::Error, path::Path};
 
@@ -50,6 +50,8 @@ struct RenderResult {
     images: ImageData,
     render_time: std::time::Duration,
     image_size: fidget::render::ImageSize,
+    /// New view hints, with the render mode seeded from them
+    view: Option<(ViewHints, RenderMode)>,
 }
 
 fn render_thread<F>(
@@ -64,8 +66,12 @@ where
     // This is our target framerate; updates faster than this will be merged.
     const DT: std::time::Duration = std::time::Duration::from_millis(16);
 
-    let mut config = None;
-    let mut script_ctx = None;
+    let mut config: Option<RenderSettings> = None;
+    let mut script_ctx: Option<ScriptContext> = None;
+
+    // The camera is reset whenever the script changes its view hints
+    let mut view: Option<ViewHints> = None;
+    let mut reseed = false;
     let mut timeout_time: Option<std::time::Instant> = None;
     loop {
         let timeout = if let Some(t) = timeout_time {
@@ -78,6 +84,10 @@ where
             recv(rx) -> msg => match msg? {
                 Ok(s) => {
                     debug!("render thread got a new result");
+                    if view.as_ref() != Some(&s.view) {
+                        view = Some(s.view.clone());
+                        reseed = true;
+                    }
                     script_ctx = Some(s);
                     if timeout_time.is_none() {
                         timeout_time = Some(std::time::Instant::now() + DT);
@@ -107,26 +117,35 @@ where
             continue;
         }
 
-        if let (Some(out), Some(render_config)) = (&script_ctx, &config) {
+        if let (Some(out), Some(render_config)) = (&script_ctx, &mut config) {
             debug!("Rendering...");
+            let seeded = if std::mem::take(&mut reseed) {
+                render_config.mode = render_config.mode.with_view(&out.view);
+                Some((out.view.clone(), render_config.mode))
+            } else {
+                None
+            };
             let render_start = std::time::Instant::now();
+            // Layers are prepared like the CLI's images, then drawn with the
+            // canvas camera, which was seeded from the view hints
             let images = match &render_config.mode {
                 RenderMode::TwoD { canvas, mode } => {
-                    let data = out
-                        .shapes
-                        .iter()
-                        .map(|s| {
-                            let tape = fidget::shape::Shape::<F>::from(s.tree.clone());
-                            render_2d(
-                                *mode,
-                                canvas.view(),
-                                tape,
-                                render_config.image_size,
-                                s.color_rgb,
-                            )
-                        })
-                        .collect();
-                    ImageData::Rgba(data)
+                    let mode = mode.render_mode();
+                    render::layers_2d(out, mode).map(|layers| {
+                        let images = layers
+                            .into_iter()
+                            .map(|(tree, color)| {
+                                render::render_layer_2d::<F>(
+                                    mode,
+                                    tree,
+                                    canvas.view(),
+                                    render_config.image_size,
+                                    color,
+                                )
+                            })
+                            .collect();
+                        ImageData::Rgba(images)
+                    })
                 }
                 RenderMode::ThreeD { canvas, mode } => {
                     // XXX allow selection of depth?
@@ -136,98 +155,38 @@ where
                         image_size.height(),
                         image_size.width().max(image_size.height()),
                     );
-                    let data = out
-                        .shapes
-                        .iter()
-                        .map(|s| {
-                            let tape = fidget::shape::Shape::<F>::from(s.tree.clone());
-                            render_3d(canvas.view(), tape, voxel_size)
-                        })
-                        .collect();
-                    ImageData::Geometry {
-                        images: data,
-                        mode: *mode,
-                        max_depth: voxel_size.depth(),
-                    }
+                    render::layers_3d(out).map(|layers| {
+                        let images = layers
+                            .into_iter()
+                            .map(|(tree, _color)| {
+                                render::render_layer_3d::<F>(tree, canvas.view(), voxel_size)
+                            })
+                            .collect();
+                        ImageData::Geometry {
+                            images,
+                            mode: *mode,
+                            max_depth: voxel_size.depth(),
+                        }
+                    })
                 }
             };
 
             let dt = render_start.elapsed();
-            tx.send(Ok(RenderResult {
-                images,
-                render_time: dt,
-                image_size: render_config.image_size,
-            }))?;
+            tx.send(
+                images
+                    .map(|images| RenderResult {
+                        images,
+                        render_time: dt,
+                        image_size: render_config.image_size,
+                        view: seeded,
+                    })
+                    .map_err(|e| e.to_string()),
+            )?;
             wake.send(()).unwrap();
         }
     }
 }
 
-fn render_2d<F: fidget::eval::Function + fidget::render::RenderHints>(
-    mode: Mode2D,
-    view: View2,
-    shape: fidget::shape::Shape<F>,
-    image_size: fidget::render::ImageSize,
-    color: [u8; 3],
-) -> Vec<[u8; 4]> {
-    let config = ImageRenderConfig {
-        image_size,
-        tile_sizes: F::tile_sizes_2d(),
-        view,
-        ..Default::default()
-    };
-
-    let out = match mode {
-        Mode2D::Color => {
-            let image = config
-                .run::<_, fidget::render::BitRenderMode>(shape)
-                .unwrap();
-            let c = [color[0], color[1], color[2], u8::MAX];
-            image.map(|p| if *p { c } else { [0u8; 4] })
-        }
-
-        Mode2D::Sdf => config
-            .run::<_, fidget::render::SdfRenderMode>(shape)
-            .unwrap()
-            .map(|&[r, g, b]| [r, g, b, u8::MAX]),
-
-        Mode2D::ExactSdf => config
-            .run::<_, fidget::render::SdfPixelRenderMode>(shape)
-            .unwrap()
-            .map(|&[r, g, b]| [r, g, b, u8::MAX]),
-
-        Mode2D::Debug => {
-            let image = config
-                .run::<_, fidget::render::DebugRenderMode>(shape)
-                .unwrap();
-            image.map(|p| p.as_debug_color())
-        }
-    };
-    let (data, _) = out.take();
-    data
-}
-
-fn render_3d<F: fidget::eval::Function + fidget::render::RenderHints>(
-    view: View3,
-    shape: fidget::shape::Shape<F>,
-    image_size: fidget::render::VoxelSize,
-) -> Vec<GeometryPixel> {
-    let config = VoxelRenderConfig {
-        image_size,
-        tile_sizes: F::tile_sizes_3d(),
-        view,
-        ..Default::default()
-    };
-
-    // Get the geometry buffer from the voxel rendering process
-    let geometry_buffer = config.run(shape).unwrap();
-
-    // For both rendering modes, we'll just pass the GeometryPixel data
-    // to the GPU, which will apply the appropriate rendering effect
-    let (data, _) = geometry_buffer.take();
-    data
-}
-
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     let args = Args::parse();
@@ -322,6 +281,15 @@ enum Mode2D {
 }
 
 impl Mode2D {
+    fn render_mode(self) -> render::Mode2D {
+        match self {
+            Self::Color => render::Mode2D::Color,
+            Self::Sdf => render::Mode2D::Sdf,
+            Self::ExactSdf => render::Mode2D::ExactSdf,
+            Self::Debug => render::Mode2D::Debug,
+        }
+    }
+
     fn description(&self) -> &'static str {
         match self {
             Self::Color => "2D color",
@@ -358,7 +326,20 @@ enum RenderMode {
 }
 
 impl RenderMode {
-    fn set_2d_mode(&mut self, new_mode: Mode2D) -> bool {
+    /// Returns the same mode, with the camera seeded from the view hints
+    fn with_view(self, view: &ViewHints) -> Self {
+        match self {
+            RenderMode::TwoD { mode, .. } => RenderMode::TwoD {
+                canvas: canvas_2d(view),
+                mode,
+            },
+            RenderMode::ThreeD { mode, .. } => RenderMode::ThreeD {
+                canvas: canvas_3d(view),
+                mode,
+            },
+        }
+    }
+    fn set_2d_mode(&mut self, new_mode: Mode2D, view: &ViewHints) -> bool {
         match self {
             RenderMode::TwoD { mode, .. } => {
                 let changed = *mode != new_mode;
@@ -368,19 +349,19 @@ impl RenderMode {
             RenderMode::ThreeD { .. } => {
                 *self = RenderMode::TwoD {
                     // TODO get parameters from 3D camera here?
-                    canvas: Canvas2::new(fidget::render::ImageSize::new(0, 0)),
+                    canvas: canvas_2d(view),
                     mode: new_mode,
                 };
                 true
             }
         }
     }
-    fn set_3d_mode(&mut self, new_mode: Mode3D) -> bool {
+    fn set_3d_mode(&mut self, new_mode: Mode3D, view: &ViewHints) -> bool {
         match self {
             RenderMode::TwoD { .. } => {
                 // TODO get parameters from 2D camera here?
                 *self = RenderMode::ThreeD {
-                    canvas: Canvas3::new(fidget::render::VoxelSize::new(0, 0, 0)),
+                    canvas: canvas_3d(view),
                     mode: new_mode,
                 };
                 true
@@ -394,6 +375,19 @@ impl RenderMode {
     }
 }
 
+/// Builds a 2D camera from the script's view hints
+fn canvas_2d(view: &ViewHints) -> Canvas2 {
+    Canvas2::from_view(view.view_2d(), fidget::render::ImageSize::new(0, 0))
+}
+
+/// Builds a 3D camera from the script's view hints
+///
+/// Rotation and projection can't be expressed by the camera; trees must be
+/// passed through [`ViewHints::orient_3d`].
+fn canvas_3d(view: &ViewHints) -> Canvas3 {
+    Canvas3::from_view(view.view_3d(), fidget::render::VoxelSize::new(0, 0, 0))
+}
+
 struct CustomTexture {
     bind_group: wgpu::BindGroup,
     texture: wgpu::Texture,
@@ -407,6 +401,9 @@ struct ViewerApp {
     mode: RenderMode,
     image_size: fidget::render::ImageSize,
 
+    /// Latest view hints from the script, used to seed new cameras
+    view: ViewHints,
+
     config_tx: Sender<RenderSettings>,
     image_rx: Receiver<Result<RenderResult, String>>,
 }
@@ -431,6 +428,7 @@ impl ViewerApp {
         Self {
             image_data: None,
             image_size,
+            view: ViewHints::default(),
 
             config_tx,
             image_rx,
@@ -455,7 +453,7 @@ impl ViewerApp {
                         ui.radio_value(&mut mode_3d, Some(m), m.description());
                     }
                     if let Some(m) = mode_3d {
-                        changed = self.mode.set_3d_mode(m);
+                        changed = self.mode.set_3d_mode(m, &self.view);
                     }
                     ui.separator();
                     let mut mode_2d = match &self.mode {
@@ -467,7 +465,7 @@ impl ViewerApp {
                     }
 
                     if let Some(m) = mode_2d {
-                        changed = self.mode.set_2d_mode(m);
+                        changed = self.mode.set_2d_mode(m, &self.view);
                     }
                 });
             });
@@ -478,7 +476,11 @@ impl ViewerApp {
     /// Try to receive an image from the worker thread, populating
     /// `self.texture` and `self.stats`, or `self.err`
     fn try_recv_image(&mut self) {
-        if let Ok(r) = self.image_rx.try_recv() {
+        if let Ok(mut r) = self.image_rx.try_recv() {
+            if let Some((view, mode)) = r.as_mut().ok().and_then(|r| r.view.take()) {
+                self.view = view;
+                self.mode = mode;
+            }
             self.image_data = Some(r);
         }
     }
@@ -14,6 +14,9 @@ pub(crate) fn koto_script_thread(
         let script = rx.recv()?;
         debug!("koto script thread received script");
         let r = engine.run(&script).map_err(|e| e.to_string());
+        if let Ok(Some(stats)) = r.as_ref().map(|ctx| ctx.optimize_stats) {
+            debug!("koto script thread optimized shapes: {stats}");
+        }
         debug!("koto script thread is sending result to render thread");
         tx.send(r)?;
     }